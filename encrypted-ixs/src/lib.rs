@@ -4,28 +4,60 @@ use arcis_imports::*;
 mod circuits {
     use arcis_imports::*;
 
+    // The program mirrors these in states/order_book_state.rs to size the
+    // account holding the encrypted book.
     pub const MAX_ORDERS: usize = 5;
-    pub const MAX_MATCHES_PER_BATCH: usize = 3;
+    pub const MAX_MATCHES_PER_BATCH: usize = 8;
+    pub const MAX_STOP_ORDERS: usize = 3;
+    // Orders per side of one overflow page (states/order_book_page.rs)
+    pub const PAGE_ORDERS: usize = 8;
+    // Every order a run can see, the top of the book plus the page it loads,
+    // can be removed at most once
+    pub const MAX_EVICTIONS_PER_BATCH: usize = 2 * MAX_ORDERS + MAX_STOP_ORDERS + 2 * PAGE_ORDERS;
 
     #[derive(Copy, Clone)]
     pub struct Order {
         pub order_id: u64, // 8
-        // pub user_pubkey: [u8; 32], //32
-        pub amount: u64, // 8
+        pub owner: [u128; 2], // 32, owner pubkey packed as two little-endian halves
+        pub amount: u64, // 8, displayed quantity left to fill
         pub price: u64, // 8
+        pub display_amount: u64, // 8, iceberg slice size, 0 for a plain order
+        pub reserve_amount: u64, // 8, hidden quantity not yet displayed
         pub order_type: u8, // 1
+        pub order_kind: u8, // 1, 0 = limit, 1 = market (price is the protection limit)
+        pub time_in_force: u8, // 1, 0 = GTC, 1 = IOC, 2 = FOK
+        pub post_only: u8, // 1, 0 = off, 1 = reject if crossing, 2 = reprice one tick away
+        pub peg: u8, // 1, 0 = none, 1 = midpoint (price is re-set at every match)
+        pub peg_limit: u64, // 8, hidden cap on a pegged price, 0 = uncapped
+        pub min_fill: u64, // 8, smallest fill the order accepts, 0 = any
+        pub expiry: u64, // 8, unix time the order stops being good, 0 = never
         pub timestamp: u64, // 8
-    } // total 65
+        pub incoming: bool, // 1, entered the book since the last match run
+        pub page: u8, // 1, overflow page the order moves to while the top of the book is full
+        pub owed: u64, // 8, collateral its fills have used: quote for a buy, base for a sell
+    } // total 119
 
     impl Order {
         pub fn empty() -> Self {
             Order {
                 order_id: 0,
-                // user_pubkey: [0u8; 32],
+                owner: [0u128; 2],
                 amount: 0,
                 price: 0,
+                display_amount: 0,
+                reserve_amount: 0,
                 order_type: 0,
+                order_kind: 0,
+                time_in_force: 0,
+                post_only: 0,
+                peg: 0,
+                peg_limit: 0,
+                min_fill: 0,
+                expiry: 0,
                 timestamp: 0,
+                incoming: false,
+                page: 0,
+                owed: 0,
             }
         }
 
@@ -36,13 +68,353 @@ mod circuits {
         pub fn is_sell(&self) -> bool {
             self.order_type == 1
         }
+
+        pub fn is_market(&self) -> bool {
+            self.order_kind == 1
+        }
+
+        pub fn is_pegged(&self) -> bool {
+            self.peg == 1
+        }
+
+        // Price used to cross and execute: a peg's midpoint, held back by its
+        // hidden limit if it has one.
+        pub fn effective_price(&self) -> u64 {
+            if !self.is_pegged() || self.peg_limit == 0 {
+                self.price
+            } else if self.is_buy() {
+                if self.price < self.peg_limit { self.price } else { self.peg_limit }
+            } else {
+                if self.price > self.peg_limit { self.price } else { self.peg_limit }
+            }
+        }
+
+        // A remainder smaller than the minimum can still be taken in full,
+        // otherwise it could never trade again. An iceberg's remainder counts
+        // its hidden reserve, not just the displayed slice.
+        pub fn accepts_fill(&self, quantity: u64) -> bool {
+            let remaining = self.total_amount();
+            let minimum = if remaining < self.min_fill {
+                remaining
+            } else {
+                self.min_fill
+            };
+            quantity >= minimum
+        }
+
+        // Whether the order trades at a uniform auction price
+        pub fn participates_at(&self, price: u64, has_reference: bool) -> bool {
+            let priced = has_reference || !self.is_pegged();
+            if self.is_buy() {
+                priced && self.effective_price() >= price
+            } else {
+                priced && self.effective_price() <= price
+            }
+        }
+
+        pub fn is_fok(&self) -> bool {
+            self.time_in_force == 2
+        }
+
+        // Orders that may only trade in the match run right after submission
+        pub fn is_immediate(&self) -> bool {
+            self.is_market() || self.time_in_force != 0
+        }
+
+        // Only plain resting limit orders leave the top of the book: immediate
+        // orders must trade in the next run and pegs follow the midpoint
+        pub fn can_page(&self) -> bool {
+            !self.is_immediate() && !self.is_pegged()
+        }
+
+        pub fn is_expired(&self, now: u64) -> bool {
+            self.expiry != 0 && self.expiry <= now
+        }
+
+        pub fn is_owned_by(&self, owner: [u128; 2]) -> bool {
+            self.owner[0] == owner[0] && self.owner[1] == owner[1]
+        }
+
+        pub fn total_amount(&self) -> u64 {
+            self.amount + self.reserve_amount
+        }
+
+        // Splits a total quantity into the displayed slice and the hidden
+        // reserve. Only resting limit orders can be icebergs; a slice of 0 or
+        // one covering the whole quantity is a plain order. A slice is never
+        // smaller than the order's minimum fill, which it could not satisfy.
+        pub fn set_quantity(&mut self, total: u64, display_amount: u64) {
+            let slice = if display_amount > 0 && display_amount < self.min_fill {
+                self.min_fill
+            } else {
+                display_amount
+            };
+            let is_iceberg = !self.is_immediate() && slice > 0 && slice < total;
+            self.amount = if is_iceberg { slice } else { total };
+            self.reserve_amount = if is_iceberg { total - slice } else { 0 };
+            self.display_amount = if is_iceberg { slice } else { 0 };
+        }
+
+        // Rewrites a resting order in place. Time priority survives only a
+        // pure size reduction; a price change or a size increase takes the
+        // new timestamp and makes the order an aggressor again.
+        pub fn amend(&mut self, amount: u64, price: u64, display_amount: u64, timestamp: u64) {
+            // For a peg the amended price is its hidden limit
+            let current_price = if self.is_pegged() { self.peg_limit } else { self.price };
+            let keeps_priority = price == current_price
+                && amount <= self.total_amount()
+                && display_amount == self.display_amount;
+            self.set_quantity(amount, display_amount);
+            if self.is_pegged() {
+                self.peg_limit = price;
+            } else {
+                self.price = price;
+            }
+            if !keeps_priority {
+                self.timestamp = timestamp;
+                self.incoming = true;
+            }
+        }
+
+        // Tops an iceberg's displayed slice back up from the reserve once it
+        // is used up, or once what is left of it is below the minimum fill.
+        // The refill gets a new timestamp, so it queues behind orders already
+        // at its price.
+        pub fn refill(&mut self, timestamp: u64) {
+            if self.reserve_amount > 0 && (self.amount == 0 || self.amount < self.min_fill) {
+                let room = self.display_amount - self.amount;
+                let slice = if room < self.reserve_amount {
+                    room
+                } else {
+                    self.reserve_amount
+                };
+                self.amount = self.amount + slice;
+                self.reserve_amount = self.reserve_amount - slice;
+                self.timestamp = timestamp;
+            }
+        }
+    }
+
+    // Both prices are still compared for market orders: a market order's
+    // price is its protection limit. Two market orders have no reference
+    // price and never trade with each other, and neither does a peg while the
+    // midpoint is undefined.
+    fn crosses(buy: &Order, sell: &Order, has_reference: bool) -> bool {
+        let priced = has_reference || !(buy.is_pegged() || sell.is_pegged());
+        priced
+            && buy.effective_price() >= sell.effective_price()
+            && !(buy.is_market() && sell.is_market())
+    }
+
+    // Whether the funds locked for an order cover it: a sell needs its full
+    // quantity of base, a buy quantity * limit of quote. An uncapped peg has
+    // no limit to bound its cost, so it is never covered.
+    fn is_covered(order_type: u8, peg: u8, amount: u64, price: u64, locked_amount: u64) -> bool {
+        let required = if order_type == 0 {
+            amount as u128 * price as u128
+        } else {
+            amount as u128
+        };
+        required <= locked_amount as u128 && !(order_type == 0 && peg == 1 && price == 0)
+    }
+
+    // The resting side of a pair is the order that was already in the book
+    // before this match run, the other one is the aggressor. Between two
+    // orders on the same footing the older one rests, the sell on equal
+    // timestamps. The flag decides first because an iceberg refill resets
+    // the timestamp of an order that has been resting all along.
+    fn buy_is_resting(buyer: &Order, seller: &Order) -> bool {
+        if buyer.incoming != seller.incoming {
+            !buyer.incoming
+        } else {
+            buyer.timestamp < seller.timestamp
+        }
+    }
+
+    // Time priority between two orders. Timestamps can tie, so the order id
+    // and owner break the tie; the result never depends on heap layout.
+    fn arrived_before(a: &Order, b: &Order) -> bool {
+        if a.timestamp != b.timestamp {
+            a.timestamp < b.timestamp
+        } else if a.order_id != b.order_id {
+            a.order_id < b.order_id
+        } else if a.owner[0] != b.owner[0] {
+            a.owner[0] < b.owner[0]
+        } else {
+            a.owner[1] < b.owner[1]
+        }
+    }
+
+    // Price a crossing pair trades at. A market order takes the other side's
+    // price and a peg trades at its own (midpoint) price; any other pair
+    // follows the market's rule:
+    //   0 = midpoint, rounded down
+    //   1 = resting price
+    //   2 = aggressor price
+    //   3 = midpoint rounded down to a tick; rounded up instead if that falls
+    //       below the sell price, and left unrounded if no tick lies between
+    //       the two prices
+    fn execution_price(
+        buyer: &Order,
+        seller: &Order,
+        buy_price: u64,
+        sell_price: u64,
+        price_rule: u8,
+        tick_size: u64,
+    ) -> u64 {
+        let midpoint = (buy_price + sell_price) / 2;
+        let buy_is_resting = buy_is_resting(buyer, seller);
+        let resting_price = if buy_is_resting { buy_price } else { sell_price };
+        let aggressor_price = if buy_is_resting { sell_price } else { buy_price };
+
+        let tick_floor = midpoint - midpoint % tick_size;
+        let tick_ceil = tick_floor + tick_size;
+        let tick_midpoint = if tick_floor >= sell_price {
+            tick_floor
+        } else if tick_ceil <= buy_price {
+            tick_ceil
+        } else {
+            midpoint
+        };
+
+        if buyer.is_market() {
+            sell_price
+        } else if seller.is_market() {
+            buy_price
+        } else if buyer.is_pegged() && !seller.is_pegged() {
+            buy_price
+        } else if seller.is_pegged() && !buyer.is_pegged() {
+            sell_price
+        } else if price_rule == 1 {
+            resting_price
+        } else if price_rule == 2 {
+            aggressor_price
+        } else if price_rule == 3 {
+            tick_midpoint
+        } else {
+            midpoint
+        }
+    }
+
+    // Plaintext market parameters of a match run
+    pub struct MatchParams {
+        pub timestamp: u64,
+        pub price_rule: u8,
+        pub tick_size: u64,
+        pub is_auction: bool,
+        pub clearing_price: u64,
+        pub min_allocation: u64,
+    }
+
+    fn fill_price(buyer: &Order, seller: &Order, params: &MatchParams) -> u64 {
+        if params.is_auction {
+            params.clearing_price
+        } else {
+            execution_price(
+                buyer,
+                seller,
+                buyer.effective_price(),
+                seller.effective_price(),
+                params.price_rule,
+                params.tick_size,
+            )
+        }
+    }
+
+    // Fills `quantity` between a buyer and a seller at `price` and returns
+    // the match. Whether the fill completes an order is read before the
+    // quantity comes off; an iceberg left with nothing displayed shows its
+    // next slice.
+    fn trade(
+        buyer: &mut Order,
+        seller: &mut Order,
+        quantity: u64,
+        price: u64,
+        match_id: u64,
+        timestamp: u64,
+    ) -> MatchedOrder {
+        // Settlement draws exactly this from each order's lock
+        let matched = MatchedOrder {
+            match_id,
+            buyer: buyer.owner,
+            seller: seller.owner,
+            buyer_order_id: buyer.order_id,
+            seller_order_id: seller.order_id,
+            quantity,
+            execution_price: price,
+            buyer_filled: buyer.total_amount() == quantity,
+            seller_filled: seller.total_amount() == quantity,
+            buyer_owed: buyer.owed + quantity * price,
+            seller_owed: seller.owed + quantity,
+        };
+        buyer.amount = buyer.amount - quantity;
+        seller.amount = seller.amount - quantity;
+        buyer.owed = matched.buyer_owed;
+        seller.owed = matched.seller_owed;
+        buyer.refill(timestamp);
+        seller.refill(timestamp);
+
+        matched
+    }
+
+    // Market orders take liquidity ahead of any limit order, then orders rank
+    // by price and time. A peg ranks at the midpoint it follows whatever its
+    // hidden limit, so pegs on one side are ranked by time only.
+    fn buy_outranks(a: &Order, b: &Order) -> bool {
+        if a.is_market() != b.is_market() {
+            a.is_market()
+        } else if a.price != b.price {
+            a.price > b.price
+        } else {
+            a.timestamp < b.timestamp
+        }
+    }
+
+    fn sell_outranks(a: &Order, b: &Order) -> bool {
+        if a.is_market() != b.is_market() {
+            a.is_market()
+        } else if a.price != b.price {
+            a.price < b.price
+        } else {
+            a.timestamp < b.timestamp
+        }
+    }
+
+    // A stop or stop-limit order waiting for its trigger. A buy stop fires
+    // once the last execution price rises to the trigger, a sell stop once it
+    // falls to it; the wrapped order then enters the book as-is.
+    #[derive(Copy, Clone)]
+    pub struct StopOrder {
+        pub order: Order,
+        pub trigger_price: u64,
+    }
+
+    impl StopOrder {
+        pub fn empty() -> Self {
+            StopOrder {
+                order: Order::empty(),
+                trigger_price: 0,
+            }
+        }
+
+        pub fn is_triggered_by(&self, last_price: u64) -> bool {
+            if self.order.is_buy() {
+                last_price >= self.trigger_price
+            } else {
+                last_price <= self.trigger_price
+            }
+        }
     }
 
+    #[derive(Copy, Clone)]
     pub struct OrderBook {
         pub buy_orders: [Order; MAX_ORDERS],
         pub buy_count: u8,
         pub sell_orders: [Order; MAX_ORDERS],
         pub sell_count: u8,
+        pub stop_orders: [StopOrder; MAX_STOP_ORDERS],
+        pub stop_count: u8,
+        pub last_price: u64, // last execution price, 0 before the first trade
     }
 
     impl OrderBook {
@@ -52,6 +424,69 @@ mod circuits {
                 buy_count: 0,
                 sell_orders: [Order::empty(); MAX_ORDERS],
                 sell_count: 0,
+                stop_orders: [StopOrder::empty(); MAX_STOP_ORDERS],
+                stop_count: 0,
+                last_price: 0,
+            }
+        }
+
+        pub fn insert_stop(&mut self, stop: StopOrder) -> bool {
+            let success = if self.stop_count >= MAX_STOP_ORDERS as u8 {
+                false
+            } else {
+                self.stop_orders[self.stop_count as usize] = stop;
+                self.stop_count += 1;
+                true
+            };
+            success
+        }
+
+        pub fn remove_stop(&mut self, order_id: u64, owner: [u128; 2]) -> bool {
+            let last_idx = if self.stop_count > 0 { self.stop_count - 1 } else { 0 };
+            let last = self.stop_orders[last_idx as usize];
+            let mut found = false;
+
+            for i in 0..MAX_STOP_ORDERS {
+                let hit = !found
+                    && i < self.stop_count as usize
+                    && self.stop_orders[i].order.order_id == order_id
+                    && self.stop_orders[i].order.is_owned_by(owner);
+                if hit {
+                    self.stop_orders[i] = last;
+                    found = true;
+                }
+            }
+
+            if found {
+                self.stop_count -= 1;
+            }
+
+            found
+        }
+
+        // Moves every stop triggered by `last_price` into the book; nothing
+        // triggers before the first trade. A stop whose side is full stays in
+        // the list and is retried after the next trade or at the next run.
+        pub fn release_stops(&mut self, timestamp: u64) {
+            for k in 0..MAX_STOP_ORDERS {
+                let i = MAX_STOP_ORDERS - 1 - k;
+                let last_idx = if self.stop_count > 0 { self.stop_count - 1 } else { 0 };
+                let stop = self.stop_orders[i];
+                let triggered = self.last_price > 0 && stop.is_triggered_by(self.last_price);
+                if i < self.stop_count as usize && triggered {
+                    let mut order = stop.order;
+                    order.timestamp = timestamp;
+                    order.incoming = true;
+                    let inserted = if order.is_buy() {
+                        self.insert_buy(order)
+                    } else {
+                        self.insert_sell(order)
+                    };
+                    if inserted {
+                        self.stop_orders[i] = self.stop_orders[last_idx as usize];
+                        self.stop_count -= 1;
+                    }
+                }
             }
         }
 
@@ -111,28 +546,142 @@ mod circuits {
             success
         }
 
-        fn compare_buy(&self, i: usize, j: usize) -> bool {
-            let a = &self.buy_orders[i];
-            let b = &self.buy_orders[j];
+        // Lowest-priority buy from `page` that may leave the top of the book
+        fn worst_pageable_buy(&self, page: u8) -> (bool, usize) {
+            let mut found = false;
+            let mut worst = 0usize;
 
-            if a.price != b.price {
-                a.price > b.price
-            } else {
-                a.timestamp < b.timestamp
+            for i in 0..MAX_ORDERS {
+                let order = self.buy_orders[i];
+                let candidate =
+                    i < self.buy_count as usize && order.can_page() && order.page == page;
+                if candidate && (!found || buy_outranks(&self.buy_orders[worst], &order)) {
+                    found = true;
+                    worst = i;
+                }
             }
+
+            (found, worst)
         }
 
-        fn compare_sell(&self, i: usize, j: usize) -> bool {
-            let a = &self.sell_orders[i];
-            let b = &self.sell_orders[j];
+        fn worst_pageable_sell(&self, page: u8) -> (bool, usize) {
+            let mut found = false;
+            let mut worst = 0usize;
+
+            for i in 0..MAX_ORDERS {
+                let order = self.sell_orders[i];
+                let candidate =
+                    i < self.sell_count as usize && order.can_page() && order.page == page;
+                if candidate && (!found || sell_outranks(&self.sell_orders[worst], &order)) {
+                    found = true;
+                    worst = i;
+                }
+            }
+
+            (found, worst)
+        }
+
+        // Adds a buy, making room in a full top of book by moving the
+        // lowest-priority order of the buy's page down to that page; that may
+        // be the new order itself. Fails when the page is full or nothing can
+        // move.
+        pub fn place_buy(&mut self, order: Order, page: &mut BookPage) -> bool {
+            let success = if self.buy_count < MAX_ORDERS as u8 {
+                self.insert_buy(order)
+            } else {
+                let (has_worst, worst) = self.worst_pageable_buy(order.page);
+                let page_incoming = !has_worst || buy_outranks(&self.buy_orders[worst], &order);
+                if page_incoming {
+                    if order.can_page() {
+                        page.insert_buy(order)
+                    } else {
+                        false
+                    }
+                } else if page.has_buy_room() {
+                    let demoted = self.take_buy(worst);
+                    self.insert_buy(order);
+                    page.insert_buy(demoted)
+                } else {
+                    false
+                }
+            };
+            success
+        }
 
-            if a.price != b.price {
-                a.price < b.price
+        pub fn place_sell(&mut self, order: Order, page: &mut BookPage) -> bool {
+            let success = if self.sell_count < MAX_ORDERS as u8 {
+                self.insert_sell(order)
             } else {
-                a.timestamp < b.timestamp
+                let (has_worst, worst) = self.worst_pageable_sell(order.page);
+                let page_incoming = !has_worst || sell_outranks(&self.sell_orders[worst], &order);
+                if page_incoming {
+                    if order.can_page() {
+                        page.insert_sell(order)
+                    } else {
+                        false
+                    }
+                } else if page.has_sell_room() {
+                    let demoted = self.take_sell(worst);
+                    self.insert_sell(order);
+                    page.insert_sell(demoted)
+                } else {
+                    false
+                }
+            };
+            success
+        }
+
+        // Brings the best orders of `page` back up: promoted while the top
+        // of the book has room, then swapped with the lowest-priority order
+        // of the same page for as long as they outrank it.
+        pub fn rebalance_buys(&mut self, page: &mut BookPage, page_index: u8) {
+            for _ in 0..MAX_ORDERS {
+                let (has_best, best) = page.best_buy();
+                let candidate = page.buy_orders[best];
+                let (has_worst, worst) = self.worst_pageable_buy(page_index);
+                let has_room = self.buy_count < MAX_ORDERS as u8;
+                let swap = !has_room
+                    && has_worst
+                    && buy_outranks(&candidate, &self.buy_orders[worst]);
+                if has_best && (has_room || swap) {
+                    page.take_buy(best);
+                    if swap {
+                        let demoted = self.take_buy(worst);
+                        page.insert_buy(demoted);
+                    }
+                    self.insert_buy(candidate);
+                }
+            }
+        }
+
+        pub fn rebalance_sells(&mut self, page: &mut BookPage, page_index: u8) {
+            for _ in 0..MAX_ORDERS {
+                let (has_best, best) = page.best_sell();
+                let candidate = page.sell_orders[best];
+                let (has_worst, worst) = self.worst_pageable_sell(page_index);
+                let has_room = self.sell_count < MAX_ORDERS as u8;
+                let swap = !has_room
+                    && has_worst
+                    && sell_outranks(&candidate, &self.sell_orders[worst]);
+                if has_best && (has_room || swap) {
+                    page.take_sell(best);
+                    if swap {
+                        let demoted = self.take_sell(worst);
+                        page.insert_sell(demoted);
+                    }
+                    self.insert_sell(candidate);
+                }
             }
         }
 
+        fn compare_buy(&self, i: usize, j: usize) -> bool {
+            buy_outranks(&self.buy_orders[i], &self.buy_orders[j])
+        }
+
+        fn compare_sell(&self, i: usize, j: usize) -> bool {
+            sell_outranks(&self.sell_orders[i], &self.sell_orders[j])
+        }
+
         fn heapify_buy(&mut self, mut i: usize) {
             let mut done = false;
             for _ in 0..MAX_ORDERS {
@@ -189,230 +738,1625 @@ mod circuits {
             }
         }
 
-        pub fn pop_buy(&mut self) -> Order {
-            let order = self.buy_orders[0];
-            self.buy_count -= 1;
-
-            if self.buy_count > 0 {
-                self.buy_orders[0] = self.buy_orders[self.buy_count as usize];
-                self.heapify_buy(0);
+        fn rebuild_buy(&mut self) {
+            for k in 0..MAX_ORDERS / 2 {
+                self.heapify_buy(MAX_ORDERS / 2 - 1 - k);
             }
+        }
 
-            order
+        fn rebuild_sell(&mut self) {
+            for k in 0..MAX_ORDERS / 2 {
+                self.heapify_sell(MAX_ORDERS / 2 - 1 - k);
+            }
         }
 
-        pub fn pop_sell(&mut self) -> Order {
-            let order = self.sell_orders[0];
-            self.sell_count -= 1;
+        // Collateral already used by the fills of a resting order, 0 if the
+        // order is not in the book
+        pub fn owed_of(&self, order_type: u8, order_id: u64, owner: [u128; 2]) -> u64 {
+            let mut owed = 0u64;
 
-            if self.sell_count > 0 {
-                self.sell_orders[0] = self.sell_orders[self.sell_count as usize];
-                self.heapify_sell(0);
+            for i in 0..MAX_ORDERS {
+                let buy = self.buy_orders[i];
+                let sell = self.sell_orders[i];
+                let buy_hit = order_type == 0
+                    && i < self.buy_count as usize
+                    && buy.order_id == order_id
+                    && buy.is_owned_by(owner);
+                let sell_hit = order_type == 1
+                    && i < self.sell_count as usize
+                    && sell.order_id == order_id
+                    && sell.is_owned_by(owner);
+                if buy_hit {
+                    owed = buy.owed;
+                }
+                if sell_hit {
+                    owed = sell.owed;
+                }
             }
 
-            order
+            owed
         }
 
-        pub fn peek_buy(&self) -> Order {
-            self.buy_orders[0]
-        }
+        // Every slot is visited so the position of the removed order is not
+        // revealed; the hit is overwritten by the last order and the heap is
+        // rebuilt from scratch.
+        pub fn remove_buy(&mut self, order_id: u64, owner: [u128; 2]) -> bool {
+            let last_idx = if self.buy_count > 0 { self.buy_count - 1 } else { 0 };
+            let last = self.buy_orders[last_idx as usize];
+            let mut found = false;
 
-        pub fn peek_sell(&self) -> Order {
-            self.sell_orders[0]
-        }
+            for i in 0..MAX_ORDERS {
+                let hit = !found
+                    && i < self.buy_count as usize
+                    && self.buy_orders[i].order_id == order_id
+                    && self.buy_orders[i].is_owned_by(owner);
+                if hit {
+                    self.buy_orders[i] = last;
+                    found = true;
+                }
+            }
 
-        pub fn has_buy(&self) -> bool {
-            self.buy_count > 0
-        }
+            if found {
+                self.buy_count -= 1;
+            }
+            self.rebuild_buy();
 
-        pub fn has_sell(&self) -> bool {
-            self.sell_count > 0
+            found
         }
-    }
 
-    #[derive(Copy, Clone)]
-    pub struct MatchedOrder {
-        pub match_id: u64,
-        // pub buyer_vault_pubkey: [u8; 32],
-        // pub seller_vault_pubkey: [u8; 32],
-        pub quantity: u64,
-        pub execution_price: u64,
-    }
+        pub fn remove_sell(&mut self, order_id: u64, owner: [u128; 2]) -> bool {
+            let last_idx = if self.sell_count > 0 { self.sell_count - 1 } else { 0 };
+            let last = self.sell_orders[last_idx as usize];
+            let mut found = false;
 
-    impl MatchedOrder {
-        pub fn empty() -> Self {
-            MatchedOrder {
-                match_id: 0,
-                // buyer_vault_pubkey: [0u8; 32],
-                // seller_vault_pubkey: [0u8; 32],
-                quantity: 0,
-                execution_price: 0,
+            for i in 0..MAX_ORDERS {
+                let hit = !found
+                    && i < self.sell_count as usize
+                    && self.sell_orders[i].order_id == order_id
+                    && self.sell_orders[i].is_owned_by(owner);
+                if hit {
+                    self.sell_orders[i] = last;
+                    found = true;
+                }
+            }
+
+            if found {
+                self.sell_count -= 1;
             }
+            self.rebuild_sell();
+
+            found
         }
-    }
 
-    pub struct MatchResult {
-        pub matches: [MatchedOrder; MAX_MATCHES_PER_BATCH],
-        pub num_matches: u8,
-    }
+        // Amends a resting buy wherever it sits in the heap
+        pub fn amend_buy(
+            &mut self,
+            order_id: u64,
+            owner: [u128; 2],
+            amount: u64,
+            price: u64,
+            display_amount: u64,
+            timestamp: u64,
+        ) -> bool {
+            let mut found = false;
 
-    impl MatchResult {
-        pub fn empty() -> Self {
-            MatchResult {
-                matches: [MatchedOrder::empty(); MAX_MATCHES_PER_BATCH],
-                num_matches: 0,
+            for i in 0..MAX_ORDERS {
+                let hit = !found
+                    && i < self.buy_count as usize
+                    && self.buy_orders[i].order_id == order_id
+                    && self.buy_orders[i].is_owned_by(owner);
+                if hit {
+                    self.buy_orders[i].amend(amount, price, display_amount, timestamp);
+                    found = true;
+                }
             }
+            self.rebuild_buy();
+
+            found
         }
 
-        // Helper to set matches one at a time
-        pub fn set_match(&mut self, index: u8, matched_order: MatchedOrder) {
-            // Manually unroll for each possible index
-            if index == 0 {
-                self.matches[0] = matched_order;
-            } else if index == 1 {
-                self.matches[1] = matched_order;
-            } else if index == 2 {
-                self.matches[2] = matched_order;
+        pub fn amend_sell(
+            &mut self,
+            order_id: u64,
+            owner: [u128; 2],
+            amount: u64,
+            price: u64,
+            display_amount: u64,
+            timestamp: u64,
+        ) -> bool {
+            let mut found = false;
+
+            for i in 0..MAX_ORDERS {
+                let hit = !found
+                    && i < self.sell_count as usize
+                    && self.sell_orders[i].order_id == order_id
+                    && self.sell_orders[i].is_owned_by(owner);
+                if hit {
+                    self.sell_orders[i].amend(amount, price, display_amount, timestamp);
+                    found = true;
+                }
             }
-            // Add more if MAX_MATCHES_PER_BATCH > 5
+            self.rebuild_sell();
+
+            found
         }
-    }
 
-    #[instruction]
-    pub fn init_order_book(mxe: Mxe) -> Enc<Mxe, OrderBook> {
-        let order_book = OrderBook::new();
-        mxe.from_arcis(order_book)
-    }
+        // Slots are walked from the back so the order moved into a freed slot
+        // has already been checked. Orders that do not fit in `evictions`
+        // stay in the book until the next batch.
+        pub fn expire_immediate_buys(&mut self, evictions: &mut Evictions) {
+            for k in 0..MAX_ORDERS {
+                let i = MAX_ORDERS - 1 - k;
+                let last_idx = if self.buy_count > 0 { self.buy_count - 1 } else { 0 };
+                let order = self.buy_orders[i];
+                if i < self.buy_count as usize && order.is_immediate() && evictions.has_room() {
+                    evictions.push(&order, 1);
+                    self.buy_orders[i] = self.buy_orders[last_idx as usize];
+                    self.buy_count -= 1;
+                }
+            }
+            self.rebuild_buy();
+        }
 
-    pub struct SensitiveOrderData {
-        pub amount: u64,
-        pub price: u64,
-    }
+        pub fn expire_immediate_sells(&mut self, evictions: &mut Evictions) {
+            for k in 0..MAX_ORDERS {
+                let i = MAX_ORDERS - 1 - k;
+                let last_idx = if self.sell_count > 0 { self.sell_count - 1 } else { 0 };
+                let order = self.sell_orders[i];
+                if i < self.sell_count as usize && order.is_immediate() && evictions.has_room() {
+                    evictions.push(&order, 1);
+                    self.sell_orders[i] = self.sell_orders[last_idx as usize];
+                    self.sell_count -= 1;
+                }
+            }
+            self.rebuild_sell();
+        }
 
-    #[instruction]
-    pub fn submit_order(
-        sensitive_ctxt: Enc<Shared, SensitiveOrderData>,
-        orderbook_ctxt: Enc<Mxe, OrderBook>,
-        order_id: u64,
-        // user_0: u64,
-        // user_1: u64,
-        // user_2: u64,
-        // user_3: u64,
-        order_type: u8,
-        timestamp: u64,
-    ) -> (Enc<Mxe, OrderBook>, bool, u8, u8) {
-        let sensitive = sensitive_ctxt.to_arcis();
-        let mut order_book = orderbook_ctxt.to_arcis();
+        // Moves every midpoint peg to the midpoint of the best non-pegged
+        // limit orders and restores heap order. Without a two-sided reference
+        // pegs rank behind every limit order and must not trade, which the
+        // returned flag tells the caller.
+        pub fn reprice_pegs(&mut self) -> bool {
+            let mut best_bid = 0u64;
+            let mut has_bid = false;
+            let mut best_ask = 0u64;
+            let mut has_ask = false;
 
-        // Build user_pubkey by extracting bytes from the four u64 values
-        // let mut temp_0 = user_0;
-        // let mut temp_1 = user_1;
-        // let mut temp_2 = user_2;
-        // let mut temp_3 = user_3;
-        
-        // let b0 = (temp_0 % 256) as u8; temp_0 >>= 8;
-        // let b1 = (temp_0 % 256) as u8; temp_0 >>= 8;
-        // let b2 = (temp_0 % 256) as u8; temp_0 >>= 8;
-        // let b3 = (temp_0 % 256) as u8; temp_0 >>= 8;
-        // let b4 = (temp_0 % 256) as u8; temp_0 >>= 8;
-        // let b5 = (temp_0 % 256) as u8; temp_0 >>= 8;
-        // let b6 = (temp_0 % 256) as u8; temp_0 >>= 8;
-        // let b7 = (temp_0 % 256) as u8;
-        
-        // let b8 = (temp_1 % 256) as u8; temp_1 >>= 8;
-        // let b9 = (temp_1 % 256) as u8; temp_1 >>= 8;
-        // let b10 = (temp_1 % 256) as u8; temp_1 >>= 8;
-        // let b11 = (temp_1 % 256) as u8; temp_1 >>= 8;
-        // let b12 = (temp_1 % 256) as u8; temp_1 >>= 8;
-        // let b13 = (temp_1 % 256) as u8; temp_1 >>= 8;
-        // let b14 = (temp_1 % 256) as u8; temp_1 >>= 8;
-        // let b15 = (temp_1 % 256) as u8;
-        
-        // let b16 = (temp_2 % 256) as u8; temp_2 >>= 8;
-        // let b17 = (temp_2 % 256) as u8; temp_2 >>= 8;
-        // let b18 = (temp_2 % 256) as u8; temp_2 >>= 8;
-        // let b19 = (temp_2 % 256) as u8; temp_2 >>= 8;
-        // let b20 = (temp_2 % 256) as u8; temp_2 >>= 8;
-        // let b21 = (temp_2 % 256) as u8; temp_2 >>= 8;
-        // let b22 = (temp_2 % 256) as u8; temp_2 >>= 8;
-        // let b23 = (temp_2 % 256) as u8;
-        
-        // let b24 = (temp_3 % 256) as u8; temp_3 >>= 8;
-        // let b25 = (temp_3 % 256) as u8; temp_3 >>= 8;
-        // let b26 = (temp_3 % 256) as u8; temp_3 >>= 8;
-        // let b27 = (temp_3 % 256) as u8; temp_3 >>= 8;
-        // let b28 = (temp_3 % 256) as u8; temp_3 >>= 8;
-        // let b29 = (temp_3 % 256) as u8; temp_3 >>= 8;
-        // let b30 = (temp_3 % 256) as u8; temp_3 >>= 8;
-        // let b31 = (temp_3 % 256) as u8;
-
-        let order = Order {
-            order_id,
-            // user_pubkey: [b0, b1, b2, b3, b4, b5, b6, b7, b8, b9, b10, b11, b12, b13, b14, b15,
-            //              b16, b17, b18, b19, b20, b21, b22, b23, b24, b25, b26, b27, b28, b29, b30, b31],
-            amount: sensitive.amount,
-            price: sensitive.price,
-            order_type,
-            timestamp,
-        };
+            for i in 0..MAX_ORDERS {
+                let buy = self.buy_orders[i];
+                if i < self.buy_count as usize
+                    && !buy.is_market()
+                    && !buy.is_pegged()
+                    && (!has_bid || buy.price > best_bid)
+                {
+                    best_bid = buy.price;
+                    has_bid = true;
+                }
 
-        let success = if order.is_buy() {
-            order_book.insert_buy(order)
+                let sell = self.sell_orders[i];
+                if i < self.sell_count as usize
+                    && !sell.is_market()
+                    && !sell.is_pegged()
+                    && (!has_ask || sell.price < best_ask)
+                {
+                    best_ask = sell.price;
+                    has_ask = true;
+                }
+            }
+
+            let has_reference = has_bid && has_ask;
+            let midpoint = (best_bid + best_ask) / 2;
+
+            for i in 0..MAX_ORDERS {
+                if self.buy_orders[i].is_pegged() {
+                    self.buy_orders[i].price = if has_reference { midpoint } else { 0 };
+                }
+                if self.sell_orders[i].is_pegged() {
+                    self.sell_orders[i].price = if has_reference { midpoint } else { u64::MAX };
+                }
+            }
+            self.rebuild_buy();
+            self.rebuild_sell();
+
+            has_reference
+        }
+
+        // Whatever is left in the book after a match run rests for the next
+        pub fn mark_resting(&mut self) {
+            for i in 0..MAX_ORDERS {
+                self.buy_orders[i].incoming = false;
+                self.sell_orders[i].incoming = false;
+            }
+        }
+
+        // Good-till-time orders past their expiry, resting or still waiting
+        // on a stop trigger
+        pub fn expire_stale(&mut self, now: u64, evictions: &mut Evictions) {
+            for k in 0..MAX_ORDERS {
+                let i = MAX_ORDERS - 1 - k;
+                let last_idx = if self.buy_count > 0 { self.buy_count - 1 } else { 0 };
+                let order = self.buy_orders[i];
+                if i < self.buy_count as usize && order.is_expired(now) && evictions.has_room() {
+                    evictions.push(&order, 3);
+                    self.buy_orders[i] = self.buy_orders[last_idx as usize];
+                    self.buy_count -= 1;
+                }
+            }
+            self.rebuild_buy();
+
+            for k in 0..MAX_ORDERS {
+                let i = MAX_ORDERS - 1 - k;
+                let last_idx = if self.sell_count > 0 { self.sell_count - 1 } else { 0 };
+                let order = self.sell_orders[i];
+                if i < self.sell_count as usize && order.is_expired(now) && evictions.has_room() {
+                    evictions.push(&order, 3);
+                    self.sell_orders[i] = self.sell_orders[last_idx as usize];
+                    self.sell_count -= 1;
+                }
+            }
+            self.rebuild_sell();
+
+            for k in 0..MAX_STOP_ORDERS {
+                let i = MAX_STOP_ORDERS - 1 - k;
+                let last_idx = if self.stop_count > 0 { self.stop_count - 1 } else { 0 };
+                let order = self.stop_orders[i].order;
+                if i < self.stop_count as usize && order.is_expired(now) && evictions.has_room() {
+                    evictions.push(&order, 3);
+                    self.stop_orders[i] = self.stop_orders[last_idx as usize];
+                    self.stop_count -= 1;
+                }
+            }
+        }
+
+        // Uniform clearing price for an auction run. Every live order price
+        // is a candidate; the one executing the most volume wins, ties go to
+        // the smallest demand/supply imbalance and then to the lower price.
+        // Returns false when nothing crosses.
+        pub fn clearing_price(&self, has_reference: bool) -> (bool, u64) {
+            let mut best_volume = 0u64;
+            let mut best_imbalance = 0u64;
+            let mut best_price = 0u64;
+
+            for c in 0..2 * MAX_ORDERS {
+                let (candidate, live) = if c < MAX_ORDERS {
+                    (self.buy_orders[c], c < self.buy_count as usize)
+                } else {
+                    (
+                        self.sell_orders[c - MAX_ORDERS],
+                        c - MAX_ORDERS < self.sell_count as usize,
+                    )
+                };
+                let price = candidate.effective_price();
+
+                let mut demand = 0u64;
+                let mut supply = 0u64;
+                for i in 0..MAX_ORDERS {
+                    let buy = self.buy_orders[i];
+                    if i < self.buy_count as usize && buy.participates_at(price, has_reference) {
+                        demand += buy.amount;
+                    }
+                    let sell = self.sell_orders[i];
+                    if i < self.sell_count as usize && sell.participates_at(price, has_reference) {
+                        supply += sell.amount;
+                    }
+                }
+
+                let volume = if demand < supply { demand } else { supply };
+                let imbalance = if demand > supply {
+                    demand - supply
+                } else {
+                    supply - demand
+                };
+                let better = volume > best_volume
+                    || (volume == best_volume
+                        && (imbalance < best_imbalance
+                            || (imbalance == best_imbalance && price < best_price)));
+                let usable = live && (has_reference || !candidate.is_pegged());
+
+                if usable && volume > 0 && better {
+                    best_volume = volume;
+                    best_imbalance = imbalance;
+                    best_price = price;
+                }
+            }
+
+            (best_volume > 0, best_price)
+        }
+
+        // Pro-rata step. The aggressor of the pair is the taker and trades
+        // against every order resting at the other side's price level, split
+        // in proportion to displayed size:
+        //   - each share is rounded down, then raised to the market's minimum
+        //     allocation, never beyond the order's size;
+        //   - shares are granted in time priority until the taker runs out;
+        //   - the rounding remainder goes to the largest order of the level,
+        //     the earlier one on equal size;
+        //   - the taker owner's own orders, shares below either side's
+        //     minimum fill and shares that would leave a fill-or-kill order
+        //     partly filled are left out.
+        // Every share takes a match slot, again in time priority; shares past
+        // the last slot are not granted. Returns the new match count and id, whether anything
+        // traded and the last execution price.
+        pub fn fill_pro_rata(
+            &mut self,
+            buy_idx: usize,
+            sell_idx: usize,
+            has_reference: bool,
+            params: &MatchParams,
+            result: &mut MatchResult,
+            match_count: u8,
+            next_match_id: u64,
+        ) -> (u8, u64, bool, u64) {
+            let pair_buy = self.buy_orders[buy_idx];
+            let pair_sell = self.sell_orders[sell_idx];
+            let taker_is_buy = !buy_is_resting(&pair_buy, &pair_sell);
+
+            let mut taker = if taker_is_buy {
+                self.take_buy(buy_idx)
+            } else {
+                self.take_sell(sell_idx)
+            };
+            let level_order = if taker_is_buy { pair_sell } else { pair_buy };
+            let level_price = level_order.effective_price();
+            let mut level = if taker_is_buy { self.sell_orders } else { self.buy_orders };
+            let mut level_count = if taker_is_buy { self.sell_count } else { self.buy_count };
+
+            let mut members = [false; MAX_ORDERS];
+            let mut total = 0u64;
+            for k in 0..MAX_ORDERS {
+                members[k] = k < level_count as usize
+                    && level[k].effective_price() == level_price
+                    && level[k].is_market() == level_order.is_market()
+                    && !level[k].is_owned_by(taker.owner)
+                    && (has_reference || !level[k].is_pegged());
+                if members[k] {
+                    total += level[k].amount;
+                }
+            }
+            let quantity = if taker.amount < total { taker.amount } else { total };
+            let divisor = if total == 0 { 1u64 } else { total };
+
+            // Position of each member in time priority, so neither the
+            // rounding nor the slots depend on where the heap keeps it
+            let mut rank = [0usize; MAX_ORDERS];
+            for k in 0..MAX_ORDERS {
+                for m in 0..MAX_ORDERS {
+                    if members[m] && arrived_before(&level[m], &level[k]) {
+                        rank[k] += 1;
+                    }
+                }
+            }
+
+            let mut shares = [0u64; MAX_ORDERS];
+            let mut granted = 0u64;
+            for r in 0..MAX_ORDERS {
+                for k in 0..MAX_ORDERS {
+                    let proportional =
+                        ((quantity as u128 * level[k].amount as u128) / divisor as u128) as u64;
+                    let floor = if params.min_allocation < level[k].amount {
+                        params.min_allocation
+                    } else {
+                        level[k].amount
+                    };
+                    let wanted = if proportional > floor { proportional } else { floor };
+                    let budget = quantity - granted;
+                    let share = if wanted < budget { wanted } else { budget };
+                    let allowed = level[k].accepts_fill(share)
+                        && taker.accepts_fill(share)
+                        && !(level[k].is_fok() && share < level[k].total_amount());
+                    if members[k] && rank[k] == r && share > 0 && allowed {
+                        shares[k] = share;
+                        granted += share;
+                    }
+                }
+            }
+
+            // A fill-or-kill member either has its whole quantity or nothing,
+            // so it cannot take the remainder
+            let mut largest = 0usize;
+            let mut has_member = false;
+            for k in 0..MAX_ORDERS {
+                let bigger = !has_member
+                    || level[k].amount > level[largest].amount
+                    || (level[k].amount == level[largest].amount
+                        && arrived_before(&level[k], &level[largest]));
+                if members[k] && !level[k].is_fok() && bigger {
+                    largest = k;
+                    has_member = true;
+                }
+            }
+            let remainder = quantity - granted;
+            for k in 0..MAX_ORDERS {
+                let room = level[k].amount - shares[k];
+                let extra = if remainder < room { remainder } else { room };
+                if has_member && k == largest && shares[k] > 0 {
+                    shares[k] += extra;
+                }
+            }
+
+            let mut count = match_count;
+            let mut match_id = next_match_id;
+            let mut traded = false;
+            let mut last_price = 0u64;
+            for r in 0..MAX_ORDERS {
+                for k in 0..MAX_ORDERS {
+                    let has_slot = (count as usize) < MAX_MATCHES_PER_BATCH;
+                    if rank[k] == r && shares[k] > 0 && has_slot {
+                        let mut buyer = if taker_is_buy { taker } else { level[k] };
+                        let mut seller = if taker_is_buy { level[k] } else { taker };
+                        let price = fill_price(&buyer, &seller, params);
+                        let matched = trade(
+                            &mut buyer,
+                            &mut seller,
+                            shares[k],
+                            price,
+                            match_id,
+                            params.timestamp,
+                        );
+                        result.set_match(count, matched);
+                        if taker_is_buy {
+                            taker = buyer;
+                            level[k] = seller;
+                        } else {
+                            level[k] = buyer;
+                            taker = seller;
+                        }
+                        count += 1;
+                        match_id += 1;
+                        traded = true;
+                        last_price = price;
+                    }
+                }
+            }
+
+            // Drop level orders that were filled completely
+            for k in 0..MAX_ORDERS {
+                let i = MAX_ORDERS - 1 - k;
+                let last_idx = if level_count > 0 { level_count - 1 } else { 0 };
+                if i < level_count as usize && level[i].amount == 0 {
+                    level[i] = level[last_idx as usize];
+                    level_count -= 1;
+                }
+            }
+            if taker_is_buy {
+                self.sell_orders = level;
+                self.sell_count = level_count;
+                self.rebuild_sell();
+            } else {
+                self.buy_orders = level;
+                self.buy_count = level_count;
+                self.rebuild_buy();
+            }
+
+            if taker.amount > 0 {
+                if taker_is_buy {
+                    self.insert_buy(taker);
+                } else {
+                    self.insert_sell(taker);
+                }
+            }
+
+            (count, match_id, traded, last_price)
+        }
+
+        // Fills `taker`, already taken out of the book, against the best
+        // sells one match slot at a time until it is done or the slots run
+        // out. A sell is eligible on the same terms as a pair in the main
+        // loop: it crosses (or, in an auction, both sides participate at the
+        // clearing price), it is not the taker owner's own order, and the
+        // fill satisfies both sides' minimums. A fill-or-kill sell is only
+        // taken if this fill completes it. Returns the new match count and
+        // id and the last execution price.
+        pub fn sweep_sells(
+            &mut self,
+            taker: &mut Order,
+            has_reference: bool,
+            params: &MatchParams,
+            result: &mut MatchResult,
+            match_count: u8,
+            next_match_id: u64,
+        ) -> (u8, u64, u64) {
+            let mut count = match_count;
+            let mut match_id = next_match_id;
+            let mut last_price = 0u64;
+
+            for _ in 0..MAX_MATCHES_PER_BATCH {
+                let mut found = false;
+                let mut best = 0usize;
+                for j in 0..MAX_ORDERS {
+                    let sell = self.sell_orders[j];
+                    let quantity = if taker.amount < sell.amount {
+                        taker.amount
+                    } else {
+                        sell.amount
+                    };
+                    let crossing = if params.is_auction {
+                        taker.participates_at(params.clearing_price, has_reference)
+                            && sell.participates_at(params.clearing_price, has_reference)
+                    } else {
+                        crosses(taker, &sell, has_reference)
+                    };
+                    let eligible = j < self.sell_count as usize
+                        && crossing
+                        && !sell.is_owned_by(taker.owner)
+                        && quantity > 0
+                        && taker.accepts_fill(quantity)
+                        && sell.accepts_fill(quantity)
+                        && !(sell.is_fok() && quantity < sell.total_amount());
+                    if eligible && (!found || self.compare_sell(j, best)) {
+                        found = true;
+                        best = j;
+                    }
+                }
+
+                if found && (count as usize) < MAX_MATCHES_PER_BATCH {
+                    let mut seller = self.take_sell(best);
+                    let quantity = if taker.amount < seller.amount {
+                        taker.amount
+                    } else {
+                        seller.amount
+                    };
+                    let price = fill_price(taker, &seller, params);
+                    let matched =
+                        trade(taker, &mut seller, quantity, price, match_id, params.timestamp);
+                    result.set_match(count, matched);
+                    if seller.amount > 0 {
+                        self.insert_sell(seller);
+                    }
+                    count += 1;
+                    match_id += 1;
+                    last_price = price;
+                }
+            }
+
+            (count, match_id, last_price)
+        }
+
+        pub fn sweep_buys(
+            &mut self,
+            taker: &mut Order,
+            has_reference: bool,
+            params: &MatchParams,
+            result: &mut MatchResult,
+            match_count: u8,
+            next_match_id: u64,
+        ) -> (u8, u64, u64) {
+            let mut count = match_count;
+            let mut match_id = next_match_id;
+            let mut last_price = 0u64;
+
+            for _ in 0..MAX_MATCHES_PER_BATCH {
+                let mut found = false;
+                let mut best = 0usize;
+                for i in 0..MAX_ORDERS {
+                    let buy = self.buy_orders[i];
+                    let quantity = if taker.amount < buy.amount {
+                        taker.amount
+                    } else {
+                        buy.amount
+                    };
+                    let crossing = if params.is_auction {
+                        taker.participates_at(params.clearing_price, has_reference)
+                            && buy.participates_at(params.clearing_price, has_reference)
+                    } else {
+                        crosses(&buy, taker, has_reference)
+                    };
+                    let eligible = i < self.buy_count as usize
+                        && crossing
+                        && !buy.is_owned_by(taker.owner)
+                        && quantity > 0
+                        && taker.accepts_fill(quantity)
+                        && buy.accepts_fill(quantity)
+                        && !(buy.is_fok() && quantity < buy.total_amount());
+                    if eligible && (!found || self.compare_buy(i, best)) {
+                        found = true;
+                        best = i;
+                    }
+                }
+
+                if found && (count as usize) < MAX_MATCHES_PER_BATCH {
+                    let mut buyer = self.take_buy(best);
+                    let quantity = if taker.amount < buyer.amount {
+                        taker.amount
+                    } else {
+                        buyer.amount
+                    };
+                    let price = fill_price(&buyer, taker, params);
+                    let matched =
+                        trade(&mut buyer, taker, quantity, price, match_id, params.timestamp);
+                    result.set_match(count, matched);
+                    if buyer.amount > 0 {
+                        self.insert_buy(buyer);
+                    }
+                    count += 1;
+                    match_id += 1;
+                    last_price = price;
+                }
+            }
+
+            (count, match_id, last_price)
+        }
+
+        pub fn pop_buy(&mut self) -> Order {
+            let order = self.buy_orders[0];
+            self.buy_count -= 1;
+
+            if self.buy_count > 0 {
+                self.buy_orders[0] = self.buy_orders[self.buy_count as usize];
+                self.heapify_buy(0);
+            }
+
+            order
+        }
+
+        pub fn pop_sell(&mut self) -> Order {
+            let order = self.sell_orders[0];
+            self.sell_count -= 1;
+
+            if self.sell_count > 0 {
+                self.sell_orders[0] = self.sell_orders[self.sell_count as usize];
+                self.heapify_sell(0);
+            }
+
+            order
+        }
+
+        // Removes the order at `index` (which may be secret) and restores
+        // heap order.
+        pub fn take_buy(&mut self, index: usize) -> Order {
+            let order = self.buy_orders[index];
+            let last_idx = if self.buy_count > 0 { self.buy_count - 1 } else { 0 };
+            self.buy_orders[index] = self.buy_orders[last_idx as usize];
+            self.buy_count -= 1;
+            self.rebuild_buy();
+
+            order
+        }
+
+        pub fn take_sell(&mut self, index: usize) -> Order {
+            let order = self.sell_orders[index];
+            let last_idx = if self.sell_count > 0 { self.sell_count - 1 } else { 0 };
+            self.sell_orders[index] = self.sell_orders[last_idx as usize];
+            self.sell_count -= 1;
+            self.rebuild_sell();
+
+            order
+        }
+
+        // Best crossing pair whose fill satisfies both sides' minimums,
+        // ranked by buy priority first and sell priority second. A top of
+        // book that cannot trade is skipped instead of ending the run. In an
+        // auction both orders must cross the clearing price rather than each
+        // other. Every pair is visited so the chosen slots are not revealed.
+        pub fn best_eligible_pair(
+            &self,
+            has_reference: bool,
+            is_auction: bool,
+            clearing_price: u64,
+        ) -> (bool, usize, usize) {
+            let mut found = false;
+            let mut best_buy = 0usize;
+            let mut best_sell = 0usize;
+
+            for i in 0..MAX_ORDERS {
+                for j in 0..MAX_ORDERS {
+                    let buy = self.buy_orders[i];
+                    let sell = self.sell_orders[j];
+                    let fill_quantity = if buy.amount < sell.amount {
+                        buy.amount
+                    } else {
+                        sell.amount
+                    };
+                    let crossing = if is_auction {
+                        buy.participates_at(clearing_price, has_reference)
+                            && sell.participates_at(clearing_price, has_reference)
+                    } else {
+                        crosses(&buy, &sell, has_reference)
+                    };
+                    let eligible = i < self.buy_count as usize
+                        && j < self.sell_count as usize
+                        && crossing
+                        && buy.accepts_fill(fill_quantity)
+                        && sell.accepts_fill(fill_quantity);
+                    let better = !found
+                        || self.compare_buy(i, best_buy)
+                        || (i == best_buy && self.compare_sell(j, best_sell));
+                    if eligible && better {
+                        found = true;
+                        best_buy = i;
+                        best_sell = j;
+                    }
+                }
+            }
+
+            (found, best_buy, best_sell)
+        }
+
+        pub fn peek_buy(&self) -> Order {
+            self.buy_orders[0]
+        }
+
+        pub fn peek_sell(&self) -> Order {
+            self.sell_orders[0]
+        }
+
+        pub fn has_buy(&self) -> bool {
+            self.buy_count > 0
+        }
+
+        pub fn has_sell(&self) -> bool {
+            self.sell_count > 0
+        }
+    }
+
+    // Overflow for a book deeper than MAX_ORDERS per side. Every order has a
+    // home page, fixed at submission, and lives either at the top of the book
+    // or in that page, so a cancel or amend only ever needs the two. Pages
+    // are unordered and never matched directly; `rebalance_book` brings their
+    // best orders back up.
+    #[derive(Copy, Clone)]
+    pub struct BookPage {
+        pub buy_orders: [Order; PAGE_ORDERS],
+        pub buy_count: u8,
+        pub sell_orders: [Order; PAGE_ORDERS],
+        pub sell_count: u8,
+    }
+
+    impl BookPage {
+        pub fn new() -> Self {
+            BookPage {
+                buy_orders: [Order::empty(); PAGE_ORDERS],
+                buy_count: 0,
+                sell_orders: [Order::empty(); PAGE_ORDERS],
+                sell_count: 0,
+            }
+        }
+
+        pub fn has_buy_room(&self) -> bool {
+            (self.buy_count as usize) < PAGE_ORDERS
+        }
+
+        pub fn has_sell_room(&self) -> bool {
+            (self.sell_count as usize) < PAGE_ORDERS
+        }
+
+        // A paged order has rested by the time it comes back up
+        pub fn insert_buy(&mut self, order: Order) -> bool {
+            let success = if self.has_buy_room() {
+                self.buy_orders[self.buy_count as usize] = order;
+                self.buy_orders[self.buy_count as usize].incoming = false;
+                self.buy_count += 1;
+                true
+            } else {
+                false
+            };
+            success
+        }
+
+        pub fn insert_sell(&mut self, order: Order) -> bool {
+            let success = if self.has_sell_room() {
+                self.sell_orders[self.sell_count as usize] = order;
+                self.sell_orders[self.sell_count as usize].incoming = false;
+                self.sell_count += 1;
+                true
+            } else {
+                false
+            };
+            success
+        }
+
+        pub fn take_buy(&mut self, index: usize) -> Order {
+            let order = self.buy_orders[index];
+            let last_idx = if self.buy_count > 0 { self.buy_count - 1 } else { 0 };
+            self.buy_orders[index] = self.buy_orders[last_idx as usize];
+            self.buy_count -= 1;
+
+            order
+        }
+
+        pub fn take_sell(&mut self, index: usize) -> Order {
+            let order = self.sell_orders[index];
+            let last_idx = if self.sell_count > 0 { self.sell_count - 1 } else { 0 };
+            self.sell_orders[index] = self.sell_orders[last_idx as usize];
+            self.sell_count -= 1;
+
+            order
+        }
+
+        // Same sweep as OrderBook::expire_stale, for the orders parked here
+        pub fn expire_stale(&mut self, now: u64, evictions: &mut Evictions) {
+            for k in 0..PAGE_ORDERS {
+                let i = PAGE_ORDERS - 1 - k;
+                let last_idx = if self.buy_count > 0 { self.buy_count - 1 } else { 0 };
+                let order = self.buy_orders[i];
+                if i < self.buy_count as usize && order.is_expired(now) && evictions.has_room() {
+                    evictions.push(&order, 3);
+                    self.buy_orders[i] = self.buy_orders[last_idx as usize];
+                    self.buy_count -= 1;
+                }
+            }
+
+            for k in 0..PAGE_ORDERS {
+                let i = PAGE_ORDERS - 1 - k;
+                let last_idx = if self.sell_count > 0 { self.sell_count - 1 } else { 0 };
+                let order = self.sell_orders[i];
+                if i < self.sell_count as usize && order.is_expired(now) && evictions.has_room() {
+                    evictions.push(&order, 3);
+                    self.sell_orders[i] = self.sell_orders[last_idx as usize];
+                    self.sell_count -= 1;
+                }
+            }
+        }
+
+        pub fn best_buy(&self) -> (bool, usize) {
+            let mut found = false;
+            let mut best = 0usize;
+            for i in 0..PAGE_ORDERS {
+                let better =
+                    !found || buy_outranks(&self.buy_orders[i], &self.buy_orders[best]);
+                if i < self.buy_count as usize && better {
+                    found = true;
+                    best = i;
+                }
+            }
+            (found, best)
+        }
+
+        pub fn best_sell(&self) -> (bool, usize) {
+            let mut found = false;
+            let mut best = 0usize;
+            for i in 0..PAGE_ORDERS {
+                let better =
+                    !found || sell_outranks(&self.sell_orders[i], &self.sell_orders[best]);
+                if i < self.sell_count as usize && better {
+                    found = true;
+                    best = i;
+                }
+            }
+            (found, best)
+        }
+
+        // Collateral already used by the fills of a paged order, 0 if the
+        // order is not on this page
+        pub fn owed_of(&self, order_type: u8, order_id: u64, owner: [u128; 2]) -> u64 {
+            let mut owed = 0u64;
+
+            for i in 0..PAGE_ORDERS {
+                let buy = self.buy_orders[i];
+                let sell = self.sell_orders[i];
+                let buy_hit = order_type == 0
+                    && i < self.buy_count as usize
+                    && buy.order_id == order_id
+                    && buy.is_owned_by(owner);
+                let sell_hit = order_type == 1
+                    && i < self.sell_count as usize
+                    && sell.order_id == order_id
+                    && sell.is_owned_by(owner);
+                if buy_hit {
+                    owed = buy.owed;
+                }
+                if sell_hit {
+                    owed = sell.owed;
+                }
+            }
+
+            owed
+        }
+
+        // Every slot is visited so the position of the removed order is not
+        // revealed; the hit is overwritten by the last order.
+        pub fn remove_buy(&mut self, order_id: u64, owner: [u128; 2]) -> bool {
+            let last_idx = if self.buy_count > 0 { self.buy_count - 1 } else { 0 };
+            let last = self.buy_orders[last_idx as usize];
+            let mut found = false;
+
+            for i in 0..PAGE_ORDERS {
+                let hit = !found
+                    && i < self.buy_count as usize
+                    && self.buy_orders[i].order_id == order_id
+                    && self.buy_orders[i].is_owned_by(owner);
+                if hit {
+                    self.buy_orders[i] = last;
+                    found = true;
+                }
+            }
+
+            if found {
+                self.buy_count -= 1;
+            }
+
+            found
+        }
+
+        pub fn remove_sell(&mut self, order_id: u64, owner: [u128; 2]) -> bool {
+            let last_idx = if self.sell_count > 0 { self.sell_count - 1 } else { 0 };
+            let last = self.sell_orders[last_idx as usize];
+            let mut found = false;
+
+            for i in 0..PAGE_ORDERS {
+                let hit = !found
+                    && i < self.sell_count as usize
+                    && self.sell_orders[i].order_id == order_id
+                    && self.sell_orders[i].is_owned_by(owner);
+                if hit {
+                    self.sell_orders[i] = last;
+                    found = true;
+                }
+            }
+
+            if found {
+                self.sell_count -= 1;
+            }
+
+            found
+        }
+
+        pub fn amend_buy(
+            &mut self,
+            order_id: u64,
+            owner: [u128; 2],
+            amount: u64,
+            price: u64,
+            display_amount: u64,
+            timestamp: u64,
+        ) -> bool {
+            let mut found = false;
+
+            for i in 0..PAGE_ORDERS {
+                let hit = !found
+                    && i < self.buy_count as usize
+                    && self.buy_orders[i].order_id == order_id
+                    && self.buy_orders[i].is_owned_by(owner);
+                if hit {
+                    self.buy_orders[i].amend(amount, price, display_amount, timestamp);
+                    found = true;
+                }
+            }
+
+            found
+        }
+
+        pub fn amend_sell(
+            &mut self,
+            order_id: u64,
+            owner: [u128; 2],
+            amount: u64,
+            price: u64,
+            display_amount: u64,
+            timestamp: u64,
+        ) -> bool {
+            let mut found = false;
+
+            for i in 0..PAGE_ORDERS {
+                let hit = !found
+                    && i < self.sell_count as usize
+                    && self.sell_orders[i].order_id == order_id
+                    && self.sell_orders[i].is_owned_by(owner);
+                if hit {
+                    self.sell_orders[i].amend(amount, price, display_amount, timestamp);
+                    found = true;
+                }
+            }
+
+            found
+        }
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct MatchedOrder {
+        pub match_id: u64,
+        pub buyer: [u128; 2],
+        pub seller: [u128; 2],
+        pub buyer_order_id: u64,
+        pub seller_order_id: u64,
+        pub quantity: u64,
+        pub execution_price: u64,
+        pub buyer_filled: bool,  // this match used up the buyer's whole quantity
+        pub seller_filled: bool,
+        pub buyer_owed: u64,  // collateral all of the buy order's fills use, this one included
+        pub seller_owed: u64,
+    }
+
+    impl MatchedOrder {
+        pub fn empty() -> Self {
+            MatchedOrder {
+                match_id: 0,
+                buyer: [0u128; 2],
+                seller: [0u128; 2],
+                buyer_order_id: 0,
+                seller_order_id: 0,
+                quantity: 0,
+                execution_price: 0,
+                buyer_filled: false,
+                seller_filled: false,
+                buyer_owed: 0,
+                seller_owed: 0,
+            }
+        }
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct MatchResult {
+        pub matches: [MatchedOrder; MAX_MATCHES_PER_BATCH],
+        pub num_matches: u8,
+    }
+
+    impl MatchResult {
+        pub fn empty() -> Self {
+            MatchResult {
+                matches: [MatchedOrder::empty(); MAX_MATCHES_PER_BATCH],
+                num_matches: 0,
+            }
+        }
+
+        // Writes a match at a secret slot; every slot is visited, so
+        // MAX_MATCHES_PER_BATCH can change without touching this.
+        pub fn set_match(&mut self, index: u8, matched_order: MatchedOrder) {
+            for i in 0..MAX_MATCHES_PER_BATCH {
+                if index as usize == i {
+                    self.matches[i] = matched_order;
+                }
+            }
+        }
+    }
+
+    // Orders removed by the circuit itself rather than by a fill or a cancel.
+    // Revealed so the program can finalize the matching `OrderAccount`s.
+    pub struct Evictions {
+        pub order_ids: [u64; MAX_EVICTIONS_PER_BATCH],
+        pub owners: [[u128; 2]; MAX_EVICTIONS_PER_BATCH],
+        pub reasons: [u8; MAX_EVICTIONS_PER_BATCH], // 1 = time in force, 2 = self-trade prevention, 3 = expiry
+        pub owed: [u64; MAX_EVICTIONS_PER_BATCH], // collateral still held for the order's unsettled fills
+        pub count: u8,
+    }
+
+    impl Evictions {
+        pub fn empty() -> Self {
+            Evictions {
+                order_ids: [0u64; MAX_EVICTIONS_PER_BATCH],
+                owners: [[0u128; 2]; MAX_EVICTIONS_PER_BATCH],
+                reasons: [0u8; MAX_EVICTIONS_PER_BATCH],
+                owed: [0u64; MAX_EVICTIONS_PER_BATCH],
+                count: 0,
+            }
+        }
+
+        pub fn has_room(&self) -> bool {
+            (self.count as usize) < MAX_EVICTIONS_PER_BATCH
+        }
+
+        pub fn push(&mut self, order: &Order, reason: u8) {
+            if self.has_room() {
+                self.order_ids[self.count as usize] = order.order_id;
+                self.owners[self.count as usize] = order.owner;
+                self.reasons[self.count as usize] = reason;
+                self.owed[self.count as usize] = order.owed;
+                self.count += 1;
+            }
+        }
+    }
+
+    #[instruction]
+    pub fn init_order_book(mxe: Mxe) -> Enc<Mxe, OrderBook> {
+        let order_book = OrderBook::new();
+        mxe.from_arcis(order_book)
+    }
+
+    #[instruction]
+    pub fn init_book_page(mxe: Mxe) -> Enc<Mxe, BookPage> {
+        let page = BookPage::new();
+        mxe.from_arcis(page)
+    }
+
+    pub struct SensitiveOrderData {
+        pub amount: u64,
+        pub price: u64,
+        pub display_amount: u64, // iceberg slice size, 0 to show the full amount
+        pub trigger_price: u64, // stop trigger, ignored unless the order is a stop
+        pub min_fill: u64, // smallest acceptable fill, 0 = any
+    }
+
+    pub struct AmendOrderData {
+        pub amount: u64,
+        pub price: u64,
+        pub display_amount: u64,
+    }
+
+    #[instruction]
+    pub fn submit_order(
+        sensitive_ctxt: Enc<Shared, SensitiveOrderData>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+        page_ctxt: Enc<Mxe, BookPage>,
+        order_id: u64,
+        owner_lo: u128,
+        owner_hi: u128,
+        order_type: u8,
+        order_kind: u8,
+        time_in_force: u8,
+        post_only: u8,
+        is_stop: u8,
+        peg: u8,
+        expiry: u64,
+        tick_size: u64,
+        timestamp: u64,
+        locked_amount: u64,
+        page_index: u8,
+    ) -> (Enc<Mxe, OrderBook>, Enc<Mxe, BookPage>, bool, u8, u8, u8, u8, u8) {
+        let sensitive = sensitive_ctxt.to_arcis();
+        let mut order_book = orderbook_ctxt.to_arcis();
+        let mut page = page_ctxt.to_arcis();
+
+        let mut order = Order {
+            order_id,
+            owner: [owner_lo, owner_hi],
+            amount: 0,
+            price: sensitive.price,
+            display_amount: 0,
+            reserve_amount: 0,
+            order_type,
+            order_kind,
+            time_in_force,
+            post_only,
+            peg,
+            peg_limit: 0,
+            min_fill: sensitive.min_fill,
+            expiry,
+            timestamp,
+            incoming: true,
+            page: page_index,
+            owed: 0,
+        };
+        // A peg's submitted price is its hidden limit; the book price is set
+        // by the next match run
+        if order.is_pegged() {
+            order.peg_limit = sensitive.price;
+        }
+        order.set_quantity(sensitive.amount, sensitive.display_amount);
+
+        // A post-only order must not take liquidity from the opposite best.
+        // Depending on the flag it is rejected or slid one tick behind it;
+        // a market order on the other side leaves no price to slide to.
+        let best_buy = order_book.peek_buy();
+        let best_sell = order_book.peek_sell();
+        let would_cross = if order.is_buy() {
+            order_book.has_sell()
+                && (best_sell.is_market() || order.price >= best_sell.effective_price())
         } else {
-            order_book.insert_sell(order)
+            order_book.has_buy()
+                && (best_buy.is_market() || order.price <= best_buy.effective_price())
+        };
+        let can_slide = if order.is_buy() {
+            !best_sell.is_market() && best_sell.effective_price() > tick_size
+        } else {
+            !best_buy.is_market()
+        };
+
+        let slid = would_cross && post_only == 2 && can_slide;
+        if slid {
+            order.price = if order.is_buy() {
+                best_sell.effective_price() - tick_size
+            } else {
+                best_buy.effective_price() + tick_size
+            };
+        }
+        let post_only_rejected = would_cross && post_only != 0 && !slid;
+
+        let uncovered = !is_covered(
+            order_type,
+            peg,
+            sensitive.amount,
+            sensitive.price,
+            locked_amount,
+        );
+
+        let success = if uncovered || post_only_rejected {
+            false
+        } else if is_stop == 1 {
+            order_book.insert_stop(StopOrder {
+                order,
+                trigger_price: sensitive.trigger_price,
+            })
+        } else if order.is_buy() {
+            order_book.place_buy(order, &mut page)
+        } else {
+            order_book.place_sell(order, &mut page)
+        };
+
+        // 0 = accepted, 1 = orderbook full (top of the book and page),
+        // 2 = post-only order would cross, 3 = not covered by the locked amount
+        let reject_reason = if uncovered {
+            3u8
+        } else if post_only_rejected {
+            2u8
+        } else if !success {
+            1u8
+        } else {
+            0u8
         };
 
         let buy_count = order_book.buy_count;
         let sell_count = order_book.sell_count;
+        let page_buy_count = page.buy_count;
+        let page_sell_count = page.sell_count;
 
         (
             orderbook_ctxt.owner.from_arcis(order_book), // Re-encrypt for MXE
+            page_ctxt.owner.from_arcis(page),
+            success.reveal(),
+            buy_count.reveal(),
+            sell_count.reveal(),
+            page_buy_count.reveal(),
+            page_sell_count.reveal(),
+            reject_reason.reveal(),
+        )
+    }
+
+    #[instruction]
+    pub fn cancel_order(
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+        page_ctxt: Enc<Mxe, BookPage>,
+        order_id: u64,
+        owner_lo: u128,
+        owner_hi: u128,
+        order_type: u8,
+    ) -> (Enc<Mxe, OrderBook>, Enc<Mxe, BookPage>, bool, u8, u8, u8, u8, u64) {
+        let mut order_book = orderbook_ctxt.to_arcis();
+        let mut page = page_ctxt.to_arcis();
+        let owner = [owner_lo, owner_hi];
+
+        // What the order's fills have used stays locked until they settle;
+        // a stop still waiting on its trigger has not traded
+        let owed = order_book.owed_of(order_type, order_id, owner)
+            + page.owed_of(order_type, order_id, owner);
+
+        // The order is resting, moved down to its page, or still waiting on
+        // its stop trigger
+        let removed = if order_type == 0 {
+            order_book.remove_buy(order_id, owner)
+        } else {
+            order_book.remove_sell(order_id, owner)
+        };
+        let paged = if removed {
+            false
+        } else if order_type == 0 {
+            page.remove_buy(order_id, owner)
+        } else {
+            page.remove_sell(order_id, owner)
+        };
+        let success = if removed || paged {
+            true
+        } else {
+            order_book.remove_stop(order_id, owner)
+        };
+
+        let buy_count = order_book.buy_count;
+        let sell_count = order_book.sell_count;
+        let page_buy_count = page.buy_count;
+        let page_sell_count = page.sell_count;
+
+        (
+            orderbook_ctxt.owner.from_arcis(order_book),
+            page_ctxt.owner.from_arcis(page),
+            success.reveal(),
+            buy_count.reveal(),
+            sell_count.reveal(),
+            page_buy_count.reveal(),
+            page_sell_count.reveal(),
+            owed.reveal(),
+        )
+    }
+
+    #[instruction]
+    pub fn amend_order(
+        sensitive_ctxt: Enc<Shared, AmendOrderData>,
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+        page_ctxt: Enc<Mxe, BookPage>,
+        order_id: u64,
+        owner_lo: u128,
+        owner_hi: u128,
+        order_type: u8,
+        peg: u8,
+        timestamp: u64,
+        locked_amount: u64,
+    ) -> (Enc<Mxe, OrderBook>, Enc<Mxe, BookPage>, bool) {
+        let sensitive = sensitive_ctxt.to_arcis();
+        let mut order_book = orderbook_ctxt.to_arcis();
+        let mut page = page_ctxt.to_arcis();
+        let owner = [owner_lo, owner_hi];
+        // locked_amount is everything ever locked for the order; part of it
+        // is already spoken for by fills, settled or not
+        let owed = order_book.owed_of(order_type, order_id, owner)
+            + page.owed_of(order_type, order_id, owner);
+        let free = if locked_amount >= owed { locked_amount - owed } else { 0 };
+        let covered = is_covered(
+            order_type,
+            peg,
+            sensitive.amount,
+            sensitive.price,
+            free,
+        );
+
+        // Amending down to zero is a cancel, which has its own instruction;
+        // an amendment must still fit in what is locked for the order. An
+        // order amended in its page stays there until the next rebalance.
+        let valid = sensitive.amount > 0 && covered;
+        let amended = if !valid {
+            false
+        } else if order_type == 0 {
+            order_book.amend_buy(
+                order_id,
+                owner,
+                sensitive.amount,
+                sensitive.price,
+                sensitive.display_amount,
+                timestamp,
+            )
+        } else {
+            order_book.amend_sell(
+                order_id,
+                owner,
+                sensitive.amount,
+                sensitive.price,
+                sensitive.display_amount,
+                timestamp,
+            )
+        };
+        let success = if !valid || amended {
+            amended
+        } else if order_type == 0 {
+            page.amend_buy(
+                order_id,
+                owner,
+                sensitive.amount,
+                sensitive.price,
+                sensitive.display_amount,
+                timestamp,
+            )
+        } else {
+            page.amend_sell(
+                order_id,
+                owner,
+                sensitive.amount,
+                sensitive.price,
+                sensitive.display_amount,
+                timestamp,
+            )
+        };
+
+        (
+            orderbook_ctxt.owner.from_arcis(order_book),
+            page_ctxt.owner.from_arcis(page),
             success.reveal(),
+        )
+    }
+
+    // Permissionless crank for one page: moves its best orders back to the
+    // top of the book as room frees up or as they outrank what is there.
+    #[instruction]
+    pub fn rebalance_book(
+        orderbook_ctxt: Enc<Mxe, OrderBook>,
+        page_ctxt: Enc<Mxe, BookPage>,
+        page_index: u8,
+    ) -> (Enc<Mxe, OrderBook>, Enc<Mxe, BookPage>, u8, u8, u8, u8) {
+        let mut order_book = orderbook_ctxt.to_arcis();
+        let mut page = page_ctxt.to_arcis();
+
+        order_book.rebalance_buys(&mut page, page_index);
+        order_book.rebalance_sells(&mut page, page_index);
+
+        let buy_count = order_book.buy_count;
+        let sell_count = order_book.sell_count;
+        let page_buy_count = page.buy_count;
+        let page_sell_count = page.sell_count;
+
+        (
+            orderbook_ctxt.owner.from_arcis(order_book),
+            page_ctxt.owner.from_arcis(page),
             buy_count.reveal(),
             sell_count.reveal(),
+            page_buy_count.reveal(),
+            page_sell_count.reveal(),
         )
     }
 
     #[instruction]
     pub fn match_orders(
         user: Shared,
+        mxe: Mxe,
         order_book_ctxt: Enc<Mxe, OrderBook>,
-    ) -> (Enc<Shared, MatchResult>, Enc<Mxe, OrderBook>) {
+        page_ctxt: Enc<Mxe, BookPage>,
+        page_index: u8,
+        timestamp: u64,
+        self_trade_policy: u8, // 0 = cancel resting, 1 = cancel incoming, 2 = cancel both
+        price_rule: u8,
+        tick_size: u64,
+        matching_mode: u8, // 0 = continuous, 1 = uniform-price auction
+        allocation: u8, // 0 = price-time, 1 = pro-rata within a price level
+        min_allocation: u64,
+    ) -> (
+        Enc<Shared, MatchResult>,
+        Enc<Mxe, OrderBook>,
+        [u64; MAX_EVICTIONS_PER_BATCH],
+        [[u128; 2]; MAX_EVICTIONS_PER_BATCH],
+        [u8; MAX_EVICTIONS_PER_BATCH],
+        [u64; MAX_EVICTIONS_PER_BATCH],
+        u8,
+        Enc<Mxe, MatchResult>,
+        Enc<Mxe, BookPage>,
+        u8,
+        u8,
+    ) {
         let mut order_book = order_book_ctxt.to_arcis();
+        let mut page = page_ctxt.to_arcis();
         let mut result = MatchResult::empty();
+        let mut evictions = Evictions::empty();
 
         let mut match_count = 0u8;
         let mut next_match_id = 0u64;
 
-        for match_idx in 0..MAX_MATCHES_PER_BATCH {
-            if order_book.has_buy() && order_book.has_sell() {
-                let buy = order_book.peek_buy();
-                let sell = order_book.peek_sell();
+        // Expired good-till-time orders never reach matching, wherever they rest
+        order_book.expire_stale(timestamp, &mut evictions);
+        page.expire_stale(timestamp, &mut evictions);
+
+        // Stops submitted since the last trade may already be triggered by it,
+        // and stops that found their side full may fit now
+        order_book.release_stops(timestamp);
+
+        // The run's page brings its best orders up into the room expiry and
+        // the last run left, or in place of worse orders homed on it
+        order_book.rebalance_buys(&mut page, page_index);
+        order_book.rebalance_sells(&mut page, page_index);
+
+        // An auction fixes pegs and the clearing price once, on the opening
+        // book; every fill of the run then trades at that single price.
+        let is_auction = matching_mode == 1;
+        let opening_reference = order_book.reprice_pegs();
+        let (has_clearing_price, clearing_price) = if is_auction {
+            order_book.clearing_price(opening_reference)
+        } else {
+            (false, 0)
+        };
+        let params = MatchParams {
+            timestamp,
+            price_rule,
+            tick_size,
+            is_auction,
+            clearing_price,
+            min_allocation,
+        };
+
+        for _ in 0..MAX_MATCHES_PER_BATCH {
+            // In continuous mode pegs follow the midpoint as fills move the book
+            let has_reference = if is_auction {
+                opening_reference
+            } else {
+                order_book.reprice_pegs()
+            };
+
+            let (pair_found, buy_idx, sell_idx) =
+                order_book.best_eligible_pair(has_reference, is_auction, clearing_price);
+            // A pro-rata step can use several slots, so the run may end early
+            let found = pair_found
+                && (!is_auction || has_clearing_price)
+                && (match_count as usize) < MAX_MATCHES_PER_BATCH;
+
+            if found {
+                let buy = order_book.buy_orders[buy_idx];
+                let sell = order_book.sell_orders[sell_idx];
+
+                // A user's buy never fills their own sell. Of the two, the
+                // aggressor is the incoming one.
+                let self_trade = buy.is_owned_by(sell.owner);
+                let buy_incoming = !buy_is_resting(&buy, &sell);
+                let cancel_buy = self_trade_policy == 2
+                    || (self_trade_policy == 1 && buy_incoming)
+                    || (self_trade_policy == 0 && !buy_incoming);
+                let cancel_sell = self_trade_policy == 2
+                    || (self_trade_policy == 1 && !buy_incoming)
+                    || (self_trade_policy == 0 && buy_incoming);
+
+                if self_trade {
+                    if cancel_buy && evictions.has_room() {
+                        let cancelled = order_book.take_buy(buy_idx);
+                        evictions.push(&cancelled, 2);
+                    }
+                    if cancel_sell && evictions.has_room() {
+                        let cancelled = order_book.take_sell(sell_idx);
+                        evictions.push(&cancelled, 2);
+                    }
+                } else if buy.is_fok() || sell.is_fok() {
+                    // A fill-or-kill order trades as a single sweep, with
+                    // the buy taking precedence when both are. The sweep is
+                    // run on a scratch copy and kept only if it fills the
+                    // order completely with the match slots left; otherwise
+                    // the order is killed and the book is left as it was.
+                    let taker_is_buy = buy.is_fok();
+                    let mut probe_book = order_book;
+                    let mut probe_result = result;
+                    let mut probe = if taker_is_buy {
+                        probe_book.take_buy(buy_idx)
+                    } else {
+                        probe_book.take_sell(sell_idx)
+                    };
+                    let (count, match_id, last_price) = if taker_is_buy {
+                        probe_book.sweep_sells(
+                            &mut probe,
+                            has_reference,
+                            &params,
+                            &mut probe_result,
+                            match_count,
+                            next_match_id,
+                        )
+                    } else {
+                        probe_book.sweep_buys(
+                            &mut probe,
+                            has_reference,
+                            &params,
+                            &mut probe_result,
+                            match_count,
+                            next_match_id,
+                        )
+                    };
+
+                    if probe.amount == 0 {
+                        order_book = probe_book;
+                        result = probe_result;
+                        match_count = count;
+                        next_match_id = match_id;
+
+                        order_book.last_price = last_price;
+                        order_book.release_stops(timestamp);
+                    } else {
+                        let killed = if taker_is_buy {
+                            order_book.take_buy(buy_idx)
+                        } else {
+                            order_book.take_sell(sell_idx)
+                        };
+                        if evictions.has_room() {
+                            evictions.push(&killed, 1);
+                        } else if taker_is_buy {
+                            order_book.insert_buy(killed);
+                        } else {
+                            order_book.insert_sell(killed);
+                        }
+                    }
+                } else if allocation == 1 {
+                    let (count, match_id, traded, last_price) = order_book.fill_pro_rata(
+                        buy_idx,
+                        sell_idx,
+                        has_reference,
+                        &params,
+                        &mut result,
+                        match_count,
+                        next_match_id,
+                    );
+                    match_count = count;
+                    next_match_id = match_id;
 
-                if buy.price >= sell.price {
-                    let mut buyer = order_book.pop_buy();
-                    let mut seller = order_book.pop_sell();
+                    if traded {
+                        order_book.last_price = last_price;
+                        order_book.release_stops(timestamp);
+                    }
+                } else {
+                    let mut buyer = order_book.take_buy(buy_idx);
+                    let mut seller = order_book.take_sell(sell_idx);
 
-                    let execution_price = (buyer.price + seller.price) / 2;
+                    let execution_price = fill_price(&buyer, &seller, &params);
                     let fill_quantity = if buyer.amount < seller.amount {
                         buyer.amount
                     } else {
                         seller.amount
                     };
 
-                    result.set_match(
-                        match_idx as u8,
-                        MatchedOrder {
-                            match_id: next_match_id,
-                            // buyer_vault_pubkey: buyer.user_pubkey,
-                            // seller_vault_pubkey: seller.user_pubkey,
-                            quantity: fill_quantity,
-                            execution_price,
-                        },
+                    let matched = trade(
+                        &mut buyer,
+                        &mut seller,
+                        fill_quantity,
+                        execution_price,
+                        next_match_id,
+                        timestamp,
                     );
-
-                    buyer.amount = buyer.amount - fill_quantity;
-                    seller.amount = seller.amount - fill_quantity;
+                    result.set_match(match_count, matched);
 
                     if buyer.amount > 0 {
                         order_book.insert_buy(buyer);
@@ -422,17 +2366,260 @@ mod circuits {
                         order_book.insert_sell(seller);
                     }
 
-                    match_count = match_idx as u8 + 1;
+                    match_count += 1;
                     next_match_id += 1;
+
+                    // Stops triggered by this print can trade in the same run
+                    order_book.last_price = execution_price;
+                    order_book.release_stops(timestamp);
                 }
             }
         }
 
         result.num_matches = match_count;
 
+        // Market, IOC and FOK remainders never rest in the book
+        order_book.expire_immediate_buys(&mut evictions);
+        order_book.expire_immediate_sells(&mut evictions);
+        // and the page fills the room this run's fills freed for the next one
+        order_book.rebalance_buys(&mut page, page_index);
+        order_book.rebalance_sells(&mut page, page_index);
+        order_book.mark_resting();
+
+        let page_buy_count = page.buy_count;
+        let page_sell_count = page.sell_count;
+
         (
             user.from_arcis(result),
             order_book_ctxt.owner.from_arcis(order_book),
+            evictions.order_ids.reveal(),
+            evictions.owners.reveal(),
+            evictions.reasons.reveal(),
+            evictions.owed.reveal(),
+            evictions.count.reveal(),
+            // Kept on-chain so each trader can later pull their own fills
+            mxe.from_arcis(result),
+            page_ctxt.owner.from_arcis(page),
+            page_buy_count.reveal(),
+            page_sell_count.reveal(),
+        )
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct Fill {
+        pub match_id: u64,
+        pub quantity: u64,
+        pub execution_price: u64,
+    }
+
+    impl Fill {
+        pub fn empty() -> Self {
+            Fill {
+                match_id: 0,
+                quantity: 0,
+                execution_price: 0,
+            }
+        }
+    }
+
+    // One order's side of a batch; counterparties are left out
+    pub struct OrderFills {
+        pub fills: [Fill; MAX_MATCHES_PER_BATCH],
+        pub num_fills: u8,
+    }
+
+    // Re-encrypts the fills of a single order from a stored batch result to
+    // the key its owner registered at submission. The program only queues
+    // this for the order's owner.
+    #[instruction]
+    pub fn reveal_fills(
+        owner_key: Shared,
+        batch_ctxt: Enc<Mxe, MatchResult>,
+        order_id: u64,
+        owner_lo: u128,
+        owner_hi: u128,
+    ) -> Enc<Shared, OrderFills> {
+        let batch = batch_ctxt.to_arcis();
+        let owner = [owner_lo, owner_hi];
+        let mut report = OrderFills {
+            fills: [Fill::empty(); MAX_MATCHES_PER_BATCH],
+            num_fills: 0,
+        };
+
+        for i in 0..MAX_MATCHES_PER_BATCH {
+            let m = batch.matches[i];
+            let as_buyer = m.buyer_order_id == order_id
+                && m.buyer[0] == owner[0]
+                && m.buyer[1] == owner[1];
+            let as_seller = m.seller_order_id == order_id
+                && m.seller[0] == owner[0]
+                && m.seller[1] == owner[1];
+            if i < batch.num_matches as usize && (as_buyer || as_seller) {
+                let fill = Fill {
+                    match_id: m.match_id,
+                    quantity: m.quantity,
+                    execution_price: m.execution_price,
+                };
+                for j in 0..MAX_MATCHES_PER_BATCH {
+                    if report.num_fills as usize == j {
+                        report.fills[j] = fill;
+                    }
+                }
+                report.num_fills += 1;
+            }
+        }
+
+        owner_key.from_arcis(report)
+    }
+
+    // Post-trade tape for markets that opt in. tape_mode 1 reveals each
+    // match's quantity and price, 2 a single entry per batch with the total
+    // quantity and the volume-weighted price (rounded down). Identities and
+    // order ids stay encrypted.
+    #[instruction]
+    pub fn reveal_tape(
+        batch_ctxt: Enc<Mxe, MatchResult>,
+        tape_mode: u8,
+    ) -> ([u64; MAX_MATCHES_PER_BATCH], [u64; MAX_MATCHES_PER_BATCH], u8) {
+        let batch = batch_ctxt.to_arcis();
+        let aggregate = tape_mode == 2;
+
+        let mut quantities = [0u64; MAX_MATCHES_PER_BATCH];
+        let mut prices = [0u64; MAX_MATCHES_PER_BATCH];
+        let mut total_quantity: u64 = 0;
+        let mut total_notional: u128 = 0;
+
+        for i in 0..MAX_MATCHES_PER_BATCH {
+            let m = batch.matches[i];
+            if i < batch.num_matches as usize {
+                quantities[i] = m.quantity;
+                prices[i] = m.execution_price;
+                total_quantity += m.quantity;
+                total_notional += m.quantity as u128 * m.execution_price as u128;
+            }
+        }
+
+        let mut count = batch.num_matches;
+        if aggregate {
+            let divisor = if total_quantity == 0 { 1 } else { total_quantity };
+            let vwap = (total_notional / divisor as u128) as u64;
+            for i in 0..MAX_MATCHES_PER_BATCH {
+                quantities[i] = if i == 0 { total_quantity } else { 0 };
+                prices[i] = if i == 0 { vwap } else { 0 };
+            }
+            count = if batch.num_matches > 0 { 1 } else { 0 };
+        }
+
+        (quantities.reveal(), prices.reveal(), count.reveal())
+    }
+
+    // Settlement instructions for markets that settle on-chain. Everything
+    // revealed here becomes visible in the token transfers anyway.
+    #[instruction]
+    pub fn reveal_settlement(
+        batch_ctxt: Enc<Mxe, MatchResult>,
+    ) -> (
+        [u64; MAX_MATCHES_PER_BATCH],
+        [[u128; 2]; MAX_MATCHES_PER_BATCH],
+        [[u128; 2]; MAX_MATCHES_PER_BATCH],
+        [u64; MAX_MATCHES_PER_BATCH],
+        [u64; MAX_MATCHES_PER_BATCH],
+        [u64; MAX_MATCHES_PER_BATCH],
+        [u64; MAX_MATCHES_PER_BATCH],
+        [bool; MAX_MATCHES_PER_BATCH],
+        [bool; MAX_MATCHES_PER_BATCH],
+        [u64; MAX_MATCHES_PER_BATCH],
+        [u64; MAX_MATCHES_PER_BATCH],
+        u8,
+    ) {
+        let batch = batch_ctxt.to_arcis();
+
+        let mut match_ids = [0u64; MAX_MATCHES_PER_BATCH];
+        let mut buyers = [[0u128; 2]; MAX_MATCHES_PER_BATCH];
+        let mut sellers = [[0u128; 2]; MAX_MATCHES_PER_BATCH];
+        let mut buyer_order_ids = [0u64; MAX_MATCHES_PER_BATCH];
+        let mut seller_order_ids = [0u64; MAX_MATCHES_PER_BATCH];
+        let mut quantities = [0u64; MAX_MATCHES_PER_BATCH];
+        let mut prices = [0u64; MAX_MATCHES_PER_BATCH];
+        let mut buyers_filled = [false; MAX_MATCHES_PER_BATCH];
+        let mut sellers_filled = [false; MAX_MATCHES_PER_BATCH];
+        let mut buyers_owed = [0u64; MAX_MATCHES_PER_BATCH];
+        let mut sellers_owed = [0u64; MAX_MATCHES_PER_BATCH];
+
+        for i in 0..MAX_MATCHES_PER_BATCH {
+            let m = batch.matches[i];
+            match_ids[i] = m.match_id;
+            buyers[i] = m.buyer;
+            sellers[i] = m.seller;
+            buyer_order_ids[i] = m.buyer_order_id;
+            seller_order_ids[i] = m.seller_order_id;
+            quantities[i] = m.quantity;
+            prices[i] = m.execution_price;
+            buyers_filled[i] = m.buyer_filled;
+            sellers_filled[i] = m.seller_filled;
+            buyers_owed[i] = m.buyer_owed;
+            sellers_owed[i] = m.seller_owed;
+        }
+
+        (
+            match_ids.reveal(),
+            buyers.reveal(),
+            sellers.reveal(),
+            buyer_order_ids.reveal(),
+            seller_order_ids.reveal(),
+            quantities.reveal(),
+            prices.reveal(),
+            buyers_filled.reveal(),
+            sellers_filled.reveal(),
+            buyers_owed.reveal(),
+            sellers_owed.reveal(),
+            batch.num_matches.reveal(),
         )
     }
+
+    // Checks a settlement claimed by the settlement bot against a stored
+    // batch result: true only if the batch holds exactly this match. Nothing
+    // else about the batch is revealed.
+    #[instruction]
+    pub fn verify_settlement(
+        batch_ctxt: Enc<Mxe, MatchResult>,
+        match_id: u64,
+        buyer_lo: u128,
+        buyer_hi: u128,
+        seller_lo: u128,
+        seller_hi: u128,
+        buyer_order_id: u64,
+        seller_order_id: u64,
+        quantity: u64,
+        execution_price: u64,
+        buyer_filled: bool,
+        seller_filled: bool,
+        buyer_owed: u64,
+        seller_owed: u64,
+    ) -> bool {
+        let batch = batch_ctxt.to_arcis();
+        let mut committed = false;
+
+        for i in 0..MAX_MATCHES_PER_BATCH {
+            let m = batch.matches[i];
+            let same = m.match_id == match_id
+                && m.buyer[0] == buyer_lo
+                && m.buyer[1] == buyer_hi
+                && m.seller[0] == seller_lo
+                && m.seller[1] == seller_hi
+                && m.buyer_order_id == buyer_order_id
+                && m.seller_order_id == seller_order_id
+                && m.quantity == quantity
+                && m.execution_price == execution_price
+                && m.buyer_filled == buyer_filled
+                && m.seller_filled == seller_filled
+                && m.buyer_owed == buyer_owed
+                && m.seller_owed == seller_owed;
+            if i < batch.num_matches as usize && same {
+                committed = true;
+            }
+        }
+
+        committed.reveal()
+    }
 }
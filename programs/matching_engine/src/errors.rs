@@ -18,4 +18,78 @@ pub enum ErrorCode {
     AlreadySettled,
     #[msg("Unauthorized settlement")]
     UnauthorizedSettlement,
+    #[msg("Only the order owner can modify it")]
+    NotOrderOwner,
+    #[msg("Order is not resting in the orderbook")]
+    OrderNotActive,
+    #[msg("Invalid order kind")]
+    InvalidOrderKind,
+    #[msg("Invalid time in force")]
+    InvalidTimeInForce,
+    #[msg("Eviction entry does not match this order")]
+    InvalidEviction,
+    #[msg("Eviction already finalized")]
+    EvictionAlreadyFinalized,
+    #[msg("Invalid post-only mode")]
+    InvalidPostOnly,
+    #[msg("Post-only is only available for GTC limit orders")]
+    PostOnlyRequiresRestingLimit,
+    #[msg("Tick size must be non-zero")]
+    InvalidTickSize,
+    #[msg("Invalid self-trade prevention policy")]
+    InvalidSelfTradePolicy,
+    #[msg("Invalid execution price rule")]
+    InvalidPriceRule,
+    #[msg("Invalid matching mode")]
+    InvalidMatchingMode,
+    #[msg("Invalid allocation mode")]
+    InvalidAllocation,
+    #[msg("Only the orderbook authority can do this")]
+    Unauthorized,
+    #[msg("Invalid stop order")]
+    InvalidStopOrder,
+    #[msg("Pegged orders must be plain limit orders")]
+    InvalidPeg,
+    #[msg("Expiry must be in the future")]
+    InvalidExpiry,
+    #[msg("Match batch has not completed yet")]
+    MatchBatchNotComplete,
+    #[msg("Invalid trade tape setting")]
+    InvalidTradeTape,
+    #[msg("Trade tape is disabled for this market")]
+    TradeTapeDisabled,
+    #[msg("Trade tape delay has not elapsed for this batch")]
+    TradeTapeDelayNotElapsed,
+    #[msg("Trade tape already published for this batch")]
+    TradeTapeAlreadyPublished,
+    #[msg("Collateral vault must hold the quote mint for buys and the base mint for sells")]
+    InvalidCollateralMint,
+    #[msg("Settlement exceeds the amount locked for the order")]
+    InsufficientLockedAmount,
+    #[msg("Settlement needs the buy order and the sell order")]
+    InvalidSettlementOrder,
+    #[msg("Invalid settlement mode")]
+    InvalidSettlementMode,
+    #[msg("This market settles on-chain")]
+    OffChainSettlementDisabled,
+    #[msg("This market settles through the settlement bot")]
+    OnChainSettlementDisabled,
+    #[msg("Settlement already revealed for this batch")]
+    SettlementAlreadyRevealed,
+    #[msg("Settlement has not been revealed for this batch")]
+    SettlementNotRevealed,
+    #[msg("Settlement does not match a verified commitment")]
+    SettlementNotVerified,
+    #[msg("Book page index out of range")]
+    InvalidBookPage,
+    #[msg("Book page has not been initialized yet")]
+    BookPageNotReady,
+    #[msg("Invalid order type")]
+    InvalidOrderType,
+    #[msg("Settlement claim is still being verified")]
+    SettlementVerificationPending,
+    #[msg("Fills already revealed for this order and batch")]
+    FillsAlreadyRevealed,
+    #[msg("Another computation on the orderbook is still in flight")]
+    OrderBookBusy,
 }
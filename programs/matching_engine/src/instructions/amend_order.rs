@@ -0,0 +1,231 @@
+use crate::errors::ErrorCode;
+use crate::instructions::pubkey_to_u128_chunks;
+use crate::states::*;
+use crate::AmendOrderCallback;
+use crate::SignerAccount;
+use crate::COMP_DEF_OFFSET_AMEND_ORDER;
+use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+const ORDERBOOK_SEED: &[u8] = b"order_book_state";
+const BOOK_PAGE_SEED: &[u8] = b"order_book_page";
+const VAULT_SEED: &[u8] = b"vault";
+const VAULT_STATE_SEED: &[u8] = b"vault_state";
+
+use crate::ID;
+use crate::ID_CONST;
+
+pub fn amend_order(
+    ctx: Context<AmendOrder>,
+    amount: [u8; 32],
+    price: [u8; 32],
+    display_amount: [u8; 32],
+    user_pubkey: [u8; 32],
+    locked_amount: u64, // lock the order holds once amended, including what its unsettled fills draw
+    computation_offset: u64,
+    order_id: u64,
+    order_nonce: u128,
+) -> Result<()> {
+    let order_account = &mut ctx.accounts.order_account;
+
+    require!(
+        order_account.status == 1 || order_account.status == 3,
+        ErrorCode::OrderNotActive
+    );
+
+    // An increase is reserved now, so the order can never rest uncovered; a
+    // reduction is released by the callback once the amendment is applied
+    let extra = locked_amount.saturating_sub(order_account.locked_amount);
+    let available = ctx
+        .accounts
+        .vault
+        .amount
+        .checked_sub(ctx.accounts.vault_state.locked_amount)
+        .ok_or(ErrorCode::InsufficientBalance)?;
+    require!(available >= extra, ErrorCode::InsufficientBalance);
+    ctx.accounts.vault_state.locked_amount = ctx
+        .accounts
+        .vault_state
+        .locked_amount
+        .checked_add(extra)
+        .ok_or(ErrorCode::Overflow)?;
+    order_account.locked_amount = order_account
+        .locked_amount
+        .checked_add(extra)
+        .ok_or(ErrorCode::Overflow)?;
+    order_account.amend_reserved = extra;
+
+    // Settled fills already took their share out of the lock; the circuit
+    // measures the amendment against everything locked minus what fills use
+    let total_locked = locked_amount
+        .checked_add(order_account.spent_amount)
+        .ok_or(ErrorCode::Overflow)?;
+    order_account.amend_locked_total = total_locked;
+
+    let owner_chunks = pubkey_to_u128_chunks(&order_account.user);
+    let order_type = order_account.order_type;
+    let peg = order_account.peg;
+
+    // Nothing else may be queued on the book or the page until the callback
+    // has written them back
+    let computation = ctx.accounts.computation_account.key();
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.orderbook_state.begin_computation(computation, now)?;
+    ctx.accounts.book_page.begin_computation(computation, now)?;
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let args = vec![
+        // Enc<Shared, AmendOrderData> - the replacement amount, price & iceberg slice
+        Argument::ArcisPubkey(user_pubkey),
+        Argument::PlaintextU128(order_nonce),
+        Argument::EncryptedU64(amount),
+        Argument::EncryptedU64(price),
+        Argument::EncryptedU64(display_amount),
+        // Enc<Mxe, OrderBook>
+        Argument::PlaintextU128(ctx.accounts.orderbook_state.orderbook_nonce),
+        Argument::Account(
+            ctx.accounts.orderbook_state.key(),
+            OrderBookState::ORDERBOOK_OFFSET,
+            OrderBookState::ORDERBOOK_LEN,
+        ),
+        // Enc<Mxe, BookPage> - the order's home page
+        Argument::PlaintextU128(ctx.accounts.book_page.page_nonce),
+        Argument::Account(
+            ctx.accounts.book_page.key(),
+            OrderBookPage::PAGE_OFFSET,
+            OrderBookPage::PAGE_LEN,
+        ),
+        Argument::PlaintextU64(order_id),
+        Argument::PlaintextU128(owner_chunks[0]),
+        Argument::PlaintextU128(owner_chunks[1]),
+        Argument::PlaintextU8(order_type),
+        Argument::PlaintextU8(peg),
+        Argument::PlaintextU64(Clock::get()?.unix_timestamp as u64),
+        // The amended order must still fit in what is locked for it
+        Argument::PlaintextU64(total_locked),
+    ];
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![AmendOrderCallback::callback_ix(&[
+            CallbackAccount {
+                pubkey: ctx.accounts.orderbook_state.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.order_account.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.book_page.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.vault_state.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.computation_account.key(),
+                is_writable: false,
+            },
+        ])],
+    )?;
+
+    msg!("Amendment queued for order {}", order_id);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("amend_order", user)]
+#[derive(Accounts)]
+#[instruction(
+    amount: [u8; 32],
+    price: [u8; 32],
+    display_amount: [u8; 32],
+    user_pubkey: [u8; 32],
+    locked_amount: u64,
+    computation_offset: u64,
+    order_id: u64,
+    order_nonce: u128,
+)]
+pub struct AmendOrder<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_AMEND_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"order",
+            order_id.to_le_bytes().as_ref(),
+            user.key().as_ref(),
+        ],
+        bump = order_account.bump,
+        has_one = user @ ErrorCode::NotOrderOwner,
+    )]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+
+    #[account(
+        seeds = [VAULT_SEED, vault_state.mint.as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [VAULT_STATE_SEED, vault_state.mint.as_ref(), user.key().as_ref()],
+        bump = vault_state.bump,
+        constraint = vault_state.mint == orderbook_state.collateral_mint(order_account.order_type)
+            @ ErrorCode::InvalidCollateralMint,
+    )]
+    pub vault_state: Box<Account<'info, VaultState>>,
+
+    #[account(
+        mut,
+        seeds = [ORDERBOOK_SEED],
+        bump = orderbook_state.bump,
+    )]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+
+    #[account(
+        mut,
+        seeds = [BOOK_PAGE_SEED, &[order_account.page]],
+        bump = book_page.bump,
+    )]
+    pub book_page: Box<Account<'info, OrderBookPage>>,
+}
@@ -0,0 +1,177 @@
+use crate::errors::ErrorCode;
+use crate::instructions::pubkey_to_u128_chunks;
+use crate::states::*;
+use crate::SignerAccount;
+use crate::VerifySettlementCallback;
+use crate::COMP_DEF_OFFSET_VERIFY_SETTLEMENT;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+const ORDERBOOK_SEED: &[u8] = b"order_book_state";
+const MATCH_BATCH_SEED: &[u8] = b"match_batch";
+const MATCH_RECORD_SEED: &[u8] = b"match_record";
+
+use crate::ID;
+use crate::ID_CONST;
+
+// First half of bot settlement: records a hash of the match the bot wants to
+// settle and has the MPC check the claim against the batch's stored result.
+// execute_settlement only moves funds for the claim that passed. The record
+// is locked while the check is in flight, so the claim that gets verified is
+// the one that was hashed.
+pub fn commit_settlement(
+    ctx: Context<CommitSettlement>,
+    computation_offset: u64,
+    batch_id: u64,
+    claim: SettlementClaim,
+) -> Result<()> {
+    require!(
+        ctx.accounts.match_batch.settlement_mode == 0,
+        ErrorCode::OffChainSettlementDisabled
+    );
+    require!(
+        ctx.accounts.settlement_authority.key() == ctx.accounts.orderbook_state.settlement_authority,
+        ErrorCode::UnauthorizedSettlement
+    );
+    require!(ctx.accounts.match_batch.is_complete, ErrorCode::MatchBatchNotComplete);
+
+    let buyer_order = &ctx.accounts.buyer_order;
+    let seller_order = &ctx.accounts.seller_order;
+    require!(
+        claim.buyer == buyer_order.user
+            && claim.buyer_order_id == buyer_order.order_id
+            && claim.seller == seller_order.user
+            && claim.seller_order_id == seller_order.order_id,
+        ErrorCode::InvalidSettlementOrder
+    );
+
+    let match_record = &mut ctx.accounts.match_record;
+    // A failed check can be retried with the right opening; a passed one is final
+    require!(!match_record.is_verified, ErrorCode::AlreadySettled);
+    require!(
+        !match_record.verification_pending,
+        ErrorCode::SettlementVerificationPending
+    );
+
+    match_record.batch_id = batch_id;
+    match_record.match_id = claim.match_id;
+    match_record.claim_hash = claim.hash(batch_id);
+    match_record.verification_pending = true;
+    match_record.is_verified = false;
+    match_record.is_settled = false;
+    match_record.bump = ctx.bumps.match_record;
+
+    let buyer_chunks = pubkey_to_u128_chunks(&claim.buyer);
+    let seller_chunks = pubkey_to_u128_chunks(&claim.seller);
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let args = vec![
+        // Enc<Mxe, MatchResult> stored by the match_orders callback
+        Argument::PlaintextU128(ctx.accounts.match_batch.match_nonce),
+        Argument::Account(
+            ctx.accounts.match_batch.key(),
+            MatchBatch::MATCH_DATA_OFFSET,
+            MatchBatch::MATCH_DATA_LEN,
+        ),
+        // The claimed match
+        Argument::PlaintextU64(claim.match_id),
+        Argument::PlaintextU128(buyer_chunks[0]),
+        Argument::PlaintextU128(buyer_chunks[1]),
+        Argument::PlaintextU128(seller_chunks[0]),
+        Argument::PlaintextU128(seller_chunks[1]),
+        Argument::PlaintextU64(claim.buyer_order_id),
+        Argument::PlaintextU64(claim.seller_order_id),
+        Argument::PlaintextU64(claim.quantity),
+        Argument::PlaintextU64(claim.execution_price),
+        Argument::PlaintextBool(claim.buyer_filled),
+        Argument::PlaintextBool(claim.seller_filled),
+        Argument::PlaintextU64(claim.buyer_owed),
+        Argument::PlaintextU64(claim.seller_owed),
+    ];
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![VerifySettlementCallback::callback_ix(&[CallbackAccount {
+            pubkey: ctx.accounts.match_record.key(),
+            is_writable: true,
+        }])],
+    )?;
+
+    msg!("Settlement check queued for match {} of batch {}", claim.match_id, batch_id);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("verify_settlement", settlement_authority)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, batch_id: u64, claim: SettlementClaim)]
+pub struct CommitSettlement<'info> {
+    #[account(mut)]
+    pub settlement_authority: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = settlement_authority,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_VERIFY_SETTLEMENT))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        seeds = [ORDERBOOK_SEED],
+        bump = orderbook_state.bump,
+    )]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(
+        seeds = [MATCH_BATCH_SEED, batch_id.to_le_bytes().as_ref()],
+        bump = match_batch.bump,
+    )]
+    pub match_batch: Box<Account<'info, MatchBatch>>,
+    #[account(
+        seeds = [b"order", buyer_order.order_id.to_le_bytes().as_ref(), buyer_order.user.as_ref()],
+        bump = buyer_order.bump,
+        constraint = buyer_order.order_type == 0 @ ErrorCode::InvalidSettlementOrder,
+    )]
+    pub buyer_order: Box<Account<'info, OrderAccount>>,
+    #[account(
+        seeds = [b"order", seller_order.order_id.to_le_bytes().as_ref(), seller_order.user.as_ref()],
+        bump = seller_order.bump,
+        constraint = seller_order.order_type == 1 @ ErrorCode::InvalidSettlementOrder,
+    )]
+    pub seller_order: Box<Account<'info, OrderAccount>>,
+    #[account(
+        init_if_needed,
+        payer = settlement_authority,
+        space = 8 + MatchRecord::INIT_SPACE,
+        seeds = [MATCH_RECORD_SEED, batch_id.to_le_bytes().as_ref(), claim.match_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub match_record: Box<Account<'info, MatchRecord>>,
+}
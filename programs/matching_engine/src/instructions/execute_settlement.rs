@@ -1,21 +1,29 @@
 use anchor_lang::prelude::*;
 use anchor_spl::token::{self, Token, TokenAccount, Transfer};
 use crate::errors::ErrorCode;
-use crate::states::MatchRecord;
+use crate::states::{MatchBatch, MatchRecord, OrderAccount, OrderBookState, SettlementClaim, VaultState};
 
-const SETTLEMENT_BOT_PUBKEY: Pubkey =  pubkey!("11111111111111111111111111111111");
-// not the vault authority but a separate authority that can only execute settlements.
+const VAULT_SEED: &[u8] = b"vault";
+const VAULT_STATE_SEED: &[u8] = b"vault_state";
+const ORDERBOOK_SEED: &[u8] = b"order_book_state";
+const MATCH_RECORD_SEED: &[u8] = b"match_record";
+const MATCH_BATCH_SEED: &[u8] = b"match_batch";
+// The settlement authority stored in OrderBookState is not the vault authority but a separate key that can only execute settlements.
 // the vault authority is the one that can execute deposits and withdrawals which is a pda derived from the main program.
 
 pub fn execute_settlement(
     ctx: Context<ExecuteSettlement>,
-    match_id: u64,
-    quantity: u64,
-    execution_price: u64,
+    batch_id: u64,
+    claim: SettlementClaim,
 ) -> Result<()> {
+    require!(
+        ctx.accounts.match_batch.settlement_mode == 0,
+        ErrorCode::OffChainSettlementDisabled
+    );
+
     // Verify settlement authority (settlement bot)
     require!(
-        ctx.accounts.settlement_authority.key() == SETTLEMENT_BOT_PUBKEY,
+        ctx.accounts.settlement_authority.key() == ctx.accounts.settlement.orderbook_state.settlement_authority,
         ErrorCode::UnauthorizedSettlement
     );
     
@@ -24,58 +32,49 @@ pub fn execute_settlement(
         !ctx.accounts.match_record.is_settled,
         ErrorCode::AlreadySettled
     );
+
+    // Only the claim commit_settlement hashed, and the MPC then verified
+    // against the batch, can move funds
+    let match_record = &ctx.accounts.match_record;
+    let settlement = &ctx.accounts.settlement;
+    require!(match_record.is_verified, ErrorCode::SettlementNotVerified);
+    require!(
+        match_record.claim_hash == claim.hash(batch_id),
+        ErrorCode::SettlementNotVerified
+    );
+    require!(
+        claim.buyer == settlement.buyer_order.user
+            && claim.buyer_order_id == settlement.buyer_order.order_id
+            && claim.seller == settlement.seller_order.user
+            && claim.seller_order_id == settlement.seller_order.order_id,
+        ErrorCode::InvalidSettlementOrder
+    );
     
-    let quote_amount = quantity
-        .checked_mul(execution_price)
+    let quote_amount = claim
+        .quantity
+        .checked_mul(claim.execution_price)
         .ok_or(ErrorCode::Overflow)?;
-    
-    // Transfer base tokens: seller → buyer
-    let base_transfer_cpi = Transfer {
-        from: ctx.accounts.seller_base_vault.to_account_info(),
-        to: ctx.accounts.buyer_base_vault.to_account_info(),
-        authority: ctx.accounts.vault_authority.to_account_info(),
-    };
-    
-    let vault_auth_bump = ctx.bumps.vault_authority;
-    let signer_seeds: &[&[&[u8]]] = &[&[
-        b"vault_authority",
-        &[vault_auth_bump],
-    ]];
-    
-    token::transfer(
-        CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            base_transfer_cpi,
-            signer_seeds,
-        ),
-        quantity,
-    )?;
-    
-    // Transfer quote tokens: buyer → seller
-    let quote_transfer_cpi = Transfer {
-        from: ctx.accounts.buyer_quote_vault.to_account_info(),
-        to: ctx.accounts.seller_quote_vault.to_account_info(),
-        authority: ctx.accounts.vault_authority.to_account_info(),
-    };
-    
-    token::transfer(
-        CpiContext::new_with_signer(
-            ctx.accounts.token_program.to_account_info(),
-            quote_transfer_cpi,
-            signer_seeds,
-        ),
+
+    let vault_authority_bump = ctx.bumps.settlement.vault_authority;
+    ctx.accounts.settlement.settle(
+        vault_authority_bump,
+        claim.quantity,
         quote_amount,
+        claim.buyer_filled,
+        claim.seller_filled,
+        claim.buyer_owed,
+        claim.seller_owed,
     )?;
-    
+
     ctx.accounts.match_record.is_settled = true;
     ctx.accounts.match_record.settlement_timestamp = Clock::get()?.unix_timestamp;
     
     emit!(SettlementExecutedEvent {
-        match_id,
-        buyer: ctx.accounts.buyer_base_vault.owner,
-        seller: ctx.accounts.seller_base_vault.owner,
-        quantity,
-        execution_price,
+        match_id: claim.match_id,
+        buyer: claim.buyer,
+        seller: claim.seller,
+        quantity: claim.quantity,
+        execution_price: claim.execution_price,
         timestamp: Clock::get()?.unix_timestamp,
     });
     
@@ -83,41 +82,225 @@ pub fn execute_settlement(
 }
 
 #[derive(Accounts)]
-#[instruction(match_id: u64)]
+#[instruction(batch_id: u64, claim: SettlementClaim)]
 pub struct ExecuteSettlement<'info> {
     #[account(mut)]
     pub settlement_authority: Signer<'info>,  
 
+    #[account(
+        mut,
+        seeds = [MATCH_RECORD_SEED, batch_id.to_le_bytes().as_ref(), claim.match_id.to_le_bytes().as_ref()],
+        bump = match_record.bump,
+    )]
+    pub match_record: Account<'info, MatchRecord>,
+
+    #[account(
+        seeds = [MATCH_BATCH_SEED, batch_id.to_le_bytes().as_ref()],
+        bump = match_batch.bump,
+    )]
+    pub match_batch: Box<Account<'info, MatchBatch>>,
+
+    pub settlement: SettlementAccounts<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+// Orders, vaults and vault states touched by one match. Shared by the
+// settlement bot path and on-chain settlement (settle_match).
+#[derive(Accounts)]
+pub struct SettlementAccounts<'info> {
     /// CHECK: PDA authority for vault
     #[account(
         seeds = [b"vault_authority"],
         bump,
     )]
     pub vault_authority: UncheckedAccount<'info>,
-    
+
     #[account(
-        init_if_needed,
-        payer = settlement_authority,
-        space = 8 + MatchRecord::INIT_SPACE,
-        seeds = [b"match_record", match_id.to_le_bytes().as_ref()],
-        bump,
+        seeds = [ORDERBOOK_SEED],
+        bump = orderbook_state.bump,
     )]
-    pub match_record: Account<'info, MatchRecord>,
-    
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+
+    // Orders whose locks back the trade
+    #[account(
+        mut,
+        seeds = [b"order", buyer_order.order_id.to_le_bytes().as_ref(), buyer_order.user.as_ref()],
+        bump = buyer_order.bump,
+        constraint = buyer_order.order_type == 0 @ ErrorCode::InvalidSettlementOrder,
+    )]
+    pub buyer_order: Box<Account<'info, OrderAccount>>,
+    #[account(
+        mut,
+        seeds = [b"order", seller_order.order_id.to_le_bytes().as_ref(), seller_order.user.as_ref()],
+        bump = seller_order.bump,
+        constraint = seller_order.order_type == 1 @ ErrorCode::InvalidSettlementOrder,
+    )]
+    pub seller_order: Box<Account<'info, OrderAccount>>,
+
     // Buyer vaults
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, orderbook_state.base_mint.as_ref(), buyer_order.user.as_ref()],
+        bump,
+    )]
     pub buyer_base_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, orderbook_state.quote_mint.as_ref(), buyer_order.user.as_ref()],
+        bump,
+    )]
     pub buyer_quote_vault: Account<'info, TokenAccount>,
-    
+    #[account(
+        mut,
+        seeds = [VAULT_STATE_SEED, orderbook_state.quote_mint.as_ref(), buyer_order.user.as_ref()],
+        bump = buyer_quote_vault_state.bump,
+    )]
+    pub buyer_quote_vault_state: Box<Account<'info, VaultState>>,
+
     // Seller vaults
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, orderbook_state.base_mint.as_ref(), seller_order.user.as_ref()],
+        bump,
+    )]
     pub seller_base_vault: Account<'info, TokenAccount>,
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [VAULT_SEED, orderbook_state.quote_mint.as_ref(), seller_order.user.as_ref()],
+        bump,
+    )]
     pub seller_quote_vault: Account<'info, TokenAccount>,
-    
+    #[account(
+        mut,
+        seeds = [VAULT_STATE_SEED, orderbook_state.base_mint.as_ref(), seller_order.user.as_ref()],
+        bump = seller_base_vault_state.bump,
+    )]
+    pub seller_base_vault_state: Box<Account<'info, VaultState>>,
+
     pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SettlementAccounts<'info> {
+    // Swaps base for quote between the two vault pairs, draws the traded
+    // amounts from the locks of the orders that backed them and records the
+    // fill on both orders. `*_owed` is what all fills of each order draw,
+    // as the MPC counted it when this match was made.
+    pub fn settle(
+        &mut self,
+        vault_authority_bump: u8,
+        quantity: u64,
+        quote_amount: u64,
+        buyer_filled: bool,
+        seller_filled: bool,
+        buyer_owed: u64,
+        seller_owed: u64,
+    ) -> Result<()> {
+        let signer_seeds: &[&[&[u8]]] = &[&[
+            b"vault_authority",
+            &[vault_authority_bump],
+        ]];
+
+        // Transfer base tokens: seller → buyer
+        token::transfer(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                Transfer {
+                    from: self.seller_base_vault.to_account_info(),
+                    to: self.buyer_base_vault.to_account_info(),
+                    authority: self.vault_authority.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            quantity,
+        )?;
+
+        // Transfer quote tokens: buyer → seller
+        token::transfer(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                Transfer {
+                    from: self.buyer_quote_vault.to_account_info(),
+                    to: self.seller_quote_vault.to_account_info(),
+                    authority: self.vault_authority.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            quote_amount,
+        )?;
+
+        self.buyer_order.locked_amount = self
+            .buyer_order
+            .locked_amount
+            .checked_sub(quote_amount)
+            .ok_or(ErrorCode::InsufficientLockedAmount)?;
+        self.seller_order.locked_amount = self
+            .seller_order
+            .locked_amount
+            .checked_sub(quantity)
+            .ok_or(ErrorCode::InsufficientLockedAmount)?;
+
+        self.buyer_quote_vault_state.locked_amount = self
+            .buyer_quote_vault_state
+            .locked_amount
+            .checked_sub(quote_amount)
+            .ok_or(ErrorCode::InsufficientLockedAmount)?;
+        self.seller_base_vault_state.locked_amount = self
+            .seller_base_vault_state
+            .locked_amount
+            .checked_sub(quantity)
+            .ok_or(ErrorCode::InsufficientLockedAmount)?;
+
+        self.buyer_order.spent_amount = self
+            .buyer_order
+            .spent_amount
+            .checked_add(quote_amount)
+            .ok_or(ErrorCode::Overflow)?;
+        self.seller_order.spent_amount = self
+            .seller_order
+            .spent_amount
+            .checked_add(quantity)
+            .ok_or(ErrorCode::Overflow)?;
+
+        record_fill(&mut self.buyer_order, &mut self.buyer_quote_vault_state, quantity, buyer_filled, buyer_owed)?;
+        record_fill(&mut self.seller_order, &mut self.seller_base_vault_state, quantity, seller_filled, seller_owed)?;
+
+        Ok(())
+    }
+}
+
+// Moves an order to partially (3) or fully (4) filled. A completed order no
+// longer counts as active, and whatever is still locked for it beyond what
+// its unsettled fills will draw (a buy that traded below its limit) goes back
+// to the vault. Fills can settle out of order, so the completing one is not
+// necessarily the last. Orders already closed out by an eviction (cancelled
+// or expired) keep their status.
+fn record_fill(
+    order: &mut OrderAccount,
+    vault_state: &mut VaultState,
+    quantity: u64,
+    filled: bool,
+    owed: u64,
+) -> Result<()> {
+    order.filled_amount = order
+        .filled_amount
+        .checked_add(quantity)
+        .ok_or(ErrorCode::Overflow)?;
+
+    if order.status != 1 && order.status != 3 {
+        return Ok(());
+    }
+
+    if filled {
+        order.status = 4;
+        vault_state.num_active_orders = vault_state.num_active_orders.saturating_sub(1);
+        let released = order.release_unowed(owed);
+        vault_state.locked_amount = vault_state.locked_amount.saturating_sub(released);
+    } else {
+        order.status = 3;
+    }
+
+    Ok(())
 }
 
 #[event]
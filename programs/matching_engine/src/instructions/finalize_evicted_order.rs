@@ -0,0 +1,99 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::states::*;
+
+const VAULT_STATE_SEED: &[u8] = b"vault_state";
+const ORDERBOOK_SEED: &[u8] = b"order_book_state";
+const MATCH_BATCH_SEED: &[u8] = b"match_batch";
+
+// Permissionless: the eviction entry revealed by the MPC is the authority here,
+// so anyone (usually the owner or the backend) can close out the order.
+pub fn finalize_evicted_order(
+    ctx: Context<FinalizeEvictedOrder>,
+    _batch_id: u64,
+    index: u8,
+) -> Result<()> {
+    let match_batch = &mut ctx.accounts.match_batch;
+    let order_account = &mut ctx.accounts.order_account;
+    let i = index as usize;
+
+    require!(
+        match_batch.is_complete && index < match_batch.num_evictions,
+        ErrorCode::InvalidEviction
+    );
+    require!(
+        !match_batch.eviction_finalized[i],
+        ErrorCode::EvictionAlreadyFinalized
+    );
+    require!(
+        match_batch.evicted_order_ids[i] == order_account.order_id
+            && match_batch.evicted_owners[i] == order_account.user,
+        ErrorCode::InvalidEviction
+    );
+
+    match_batch.eviction_finalized[i] = true;
+    order_account.status = if match_batch.eviction_reasons[i] == 2 {
+        2  // Cancelled (self-trade prevention)
+    } else {
+        5  // Expired (time in force or expiry)
+    };
+
+    // The order no longer rests in the book, so its funds are free again,
+    // except what its unsettled fills will still draw
+    let released = order_account.release_unowed(match_batch.evicted_owed[i]);
+    let vault_state = &mut ctx.accounts.vault_state;
+    vault_state.num_active_orders = vault_state.num_active_orders.saturating_sub(1);
+    vault_state.locked_amount = vault_state.locked_amount.saturating_sub(released);
+
+    emit!(OrderExpiredEvent {
+        order_id: order_account.order_id,
+        user: order_account.user,
+        batch_id: match_batch.batch_id,
+        reason: match_batch.eviction_reasons[i],
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(batch_id: u64)]
+pub struct FinalizeEvictedOrder<'info> {
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MATCH_BATCH_SEED, batch_id.to_le_bytes().as_ref()],
+        bump = match_batch.bump,
+    )]
+    pub match_batch: Box<Account<'info, MatchBatch>>,
+    #[account(
+        mut,
+        seeds = [
+            b"order",
+            order_account.order_id.to_le_bytes().as_ref(),
+            order_account.user.as_ref(),
+        ],
+        bump = order_account.bump,
+    )]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+    #[account(
+        seeds = [ORDERBOOK_SEED],
+        bump = orderbook_state.bump,
+    )]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(
+        mut,
+        seeds = [VAULT_STATE_SEED, vault_state.mint.as_ref(), order_account.user.as_ref()],
+        bump = vault_state.bump,
+        constraint = vault_state.mint == orderbook_state.collateral_mint(order_account.order_type)
+            @ ErrorCode::InvalidCollateralMint,
+    )]
+    pub vault_state: Box<Account<'info, VaultState>>,
+}
+
+#[event]
+pub struct OrderExpiredEvent {
+    pub order_id: u64,
+    pub user: Pubkey,
+    pub batch_id: u64,
+    pub reason: u8,
+}
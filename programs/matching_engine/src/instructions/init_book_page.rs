@@ -0,0 +1,104 @@
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::COMP_DEF_OFFSET_INIT_BOOK_PAGE;
+use crate::SignerAccount;
+use crate::InitBookPageCallback;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+const ORDERBOOK_SEED: &[u8] = b"order_book_state";
+const BOOK_PAGE_SEED: &[u8] = b"order_book_page";
+use crate::ID;
+use crate::ID_CONST;
+
+pub fn init_book_page(
+    ctx: Context<InitBookPage>,
+    computation_offset: u64,
+    page_index: u8,
+) -> Result<()> {
+    require!(page_index < MAX_BOOK_PAGES, ErrorCode::InvalidBookPage);
+
+    let book_page = &mut ctx.accounts.book_page;
+    book_page.page_index = page_index;
+    book_page.page_nonce = 0;
+    book_page.buy_count = 0;
+    book_page.sell_count = 0;
+    book_page.is_ready = false;
+    book_page.bump = ctx.bumps.book_page;
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let args = vec![
+        Argument::PlaintextU128(0), // Initial nonce
+    ];
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![InitBookPageCallback::callback_ix(&[
+            CallbackAccount {
+                pubkey: ctx.accounts.book_page.key(),
+                is_writable: true,
+            },
+        ])],
+    )?;
+
+    msg!("Book page {} queued for initialization", page_index);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("init_book_page", authority)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, page_index: u8)]
+pub struct InitBookPage<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = authority,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_BOOK_PAGE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        seeds = [ORDERBOOK_SEED],
+        bump = orderbook_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + OrderBookPage::INIT_SPACE,
+        seeds = [BOOK_PAGE_SEED, &[page_index]],
+        bump,
+    )]
+    pub book_page: Box<Account<'info, OrderBookPage>>,
+}
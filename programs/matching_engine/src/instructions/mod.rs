@@ -20,4 +20,41 @@ pub mod initialize_vault;
 pub use initialize_vault::*;
 
 pub mod execute_settlement;
-pub use execute_settlement::*;
\ No newline at end of file
+pub use execute_settlement::*;
+
+pub mod cancel_order;
+pub use cancel_order::*;
+
+pub mod amend_order;
+pub use amend_order::*;
+
+pub mod finalize_evicted_order;
+pub use finalize_evicted_order::*;
+
+pub mod update_market_config;
+pub use update_market_config::*;
+
+
+pub mod reveal_fills;
+pub use reveal_fills::*;
+
+pub mod publish_trade_tape;
+pub use publish_trade_tape::*;
+
+pub mod reveal_settlement;
+pub use reveal_settlement::*;
+
+pub mod settle_match;
+pub use settle_match::*;
+
+pub mod commit_settlement;
+pub use commit_settlement::*;
+
+pub mod set_settlement_authority;
+pub use set_settlement_authority::*;
+
+pub mod init_book_page;
+pub use init_book_page::*;
+
+pub mod rebalance_book;
+pub use rebalance_book::*;
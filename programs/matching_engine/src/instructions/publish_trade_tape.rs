@@ -0,0 +1,130 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::RevealTapeCallback;
+use crate::SignerAccount;
+use crate::COMP_DEF_OFFSET_REVEAL_TAPE;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+const ORDERBOOK_SEED: &[u8] = b"order_book_state";
+const MATCH_BATCH_SEED: &[u8] = b"match_batch";
+const TRADE_TAPE_SEED: &[u8] = b"trade_tape";
+
+use crate::ID;
+use crate::ID_CONST;
+
+// Permissionless, like finalize_evicted_order: the tape setting recorded when
+// the batch was matched and the market's delay decide whether and when its
+// trades become public, so anyone (usually an indexer) can crank it. Batches
+// matched before the market opted in stay private.
+pub fn publish_trade_tape(
+    ctx: Context<PublishTradeTape>,
+    computation_offset: u64,
+    batch_id: u64,
+) -> Result<()> {
+    let config = ctx.accounts.orderbook_state.config;
+    let match_batch = &ctx.accounts.match_batch;
+
+    require!(match_batch.trade_tape != 0, ErrorCode::TradeTapeDisabled);
+    require!(match_batch.is_complete, ErrorCode::MatchBatchNotComplete);
+    require!(!match_batch.tape_published, ErrorCode::TradeTapeAlreadyPublished);
+    require!(
+        Clock::get()?.unix_timestamp >= match_batch.timestamp + config.tape_delay,
+        ErrorCode::TradeTapeDelayNotElapsed
+    );
+
+    let trade_tape = &mut ctx.accounts.trade_tape;
+    trade_tape.bump = ctx.bumps.trade_tape;
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let args = vec![
+        // Enc<Mxe, MatchResult> stored by the match_orders callback
+        Argument::PlaintextU128(ctx.accounts.match_batch.match_nonce),
+        Argument::Account(
+            ctx.accounts.match_batch.key(),
+            MatchBatch::MATCH_DATA_OFFSET,
+            MatchBatch::MATCH_DATA_LEN,
+        ),
+        Argument::PlaintextU8(ctx.accounts.match_batch.trade_tape),
+    ];
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![RevealTapeCallback::callback_ix(&[
+            CallbackAccount {
+                pubkey: ctx.accounts.match_batch.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.trade_tape.key(),
+                is_writable: true,
+            },
+        ])],
+    )?;
+
+    msg!("Trade tape queued for batch {}", batch_id);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("reveal_tape", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, batch_id: u64)]
+pub struct PublishTradeTape<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_TAPE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        seeds = [ORDERBOOK_SEED],
+        bump = orderbook_state.bump,
+    )]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(
+        seeds = [MATCH_BATCH_SEED, batch_id.to_le_bytes().as_ref()],
+        bump = match_batch.bump,
+    )]
+    pub match_batch: Box<Account<'info, MatchBatch>>,
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + TradeTape::INIT_SPACE,
+        seeds = [TRADE_TAPE_SEED],
+        bump,
+    )]
+    pub trade_tape: Box<Account<'info, TradeTape>>,
+}
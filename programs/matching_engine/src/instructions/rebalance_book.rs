@@ -0,0 +1,126 @@
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::COMP_DEF_OFFSET_REBALANCE_BOOK;
+use crate::SignerAccount;
+use crate::RebalanceBookCallback;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+const ORDERBOOK_SEED: &[u8] = b"order_book_state";
+const BOOK_PAGE_SEED: &[u8] = b"order_book_page";
+use crate::ID;
+use crate::ID_CONST;
+
+// Permissionless: anyone can pay to bring a page's best orders back to the
+// top of the book, typically right after a match run has freed room.
+pub fn rebalance_book(
+    ctx: Context<RebalanceBook>,
+    computation_offset: u64,
+    page_index: u8,
+) -> Result<()> {
+    let book_page = &ctx.accounts.book_page;
+    require!(book_page.is_ready, ErrorCode::BookPageNotReady);
+
+    // Nothing else may be queued on the book or the page until the callback
+    // has written them back
+    let computation = ctx.accounts.computation_account.key();
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.orderbook_state.begin_computation(computation, now)?;
+    ctx.accounts.book_page.begin_computation(computation, now)?;
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let args = vec![
+        // Enc<Mxe, OrderBook>
+        Argument::PlaintextU128(ctx.accounts.orderbook_state.orderbook_nonce),
+        Argument::Account(
+            ctx.accounts.orderbook_state.key(),
+            OrderBookState::ORDERBOOK_OFFSET,
+            OrderBookState::ORDERBOOK_LEN,
+        ),
+        // Enc<Mxe, BookPage>
+        Argument::PlaintextU128(book_page.page_nonce),
+        Argument::Account(
+            book_page.key(),
+            OrderBookPage::PAGE_OFFSET,
+            OrderBookPage::PAGE_LEN,
+        ),
+        Argument::PlaintextU8(page_index),
+    ];
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![RebalanceBookCallback::callback_ix(&[
+            CallbackAccount {
+                pubkey: ctx.accounts.orderbook_state.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.book_page.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.computation_account.key(),
+                is_writable: false,
+            },
+        ])],
+    )?;
+
+    msg!("Rebalance queued for book page {}", page_index);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("rebalance_book", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, page_index: u8)]
+pub struct RebalanceBook<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REBALANCE_BOOK))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(
+        mut,
+        seeds = [ORDERBOOK_SEED],
+        bump = orderbook_state.bump,
+    )]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(
+        mut,
+        seeds = [BOOK_PAGE_SEED, &[page_index]],
+        bump = book_page.bump,
+    )]
+    pub book_page: Box<Account<'info, OrderBookPage>>,
+}
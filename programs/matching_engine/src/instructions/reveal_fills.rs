@@ -0,0 +1,146 @@
+use crate::errors::ErrorCode;
+use crate::instructions::pubkey_to_u128_chunks;
+use crate::states::*;
+use crate::RevealFillsCallback;
+use crate::SignerAccount;
+use crate::COMP_DEF_OFFSET_REVEAL_FILLS;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+const MATCH_BATCH_SEED: &[u8] = b"match_batch";
+const FILL_RECEIPT_SEED: &[u8] = b"fill_receipt";
+
+use crate::ID;
+use crate::ID_CONST;
+
+// Owner-gated: re-encrypts this order's fills from the batch's stored
+// Enc<Mxe, MatchResult> to the key the order was submitted with, so neither
+// the backend nor the counterparty is involved.
+pub fn reveal_fills(
+    ctx: Context<RevealFills>,
+    computation_offset: u64,
+    batch_id: u64,
+    order_id: u64,
+    fills_nonce: u128,
+) -> Result<()> {
+    let match_batch = &ctx.accounts.match_batch;
+    let order_account = &ctx.accounts.order_account;
+
+    require!(match_batch.is_complete, ErrorCode::MatchBatchNotComplete);
+    // A reveal whose computation was dropped can be queued again until one
+    // lands
+    require!(!ctx.accounts.fill_receipt.is_ready, ErrorCode::FillsAlreadyRevealed);
+
+    let owner_chunks = pubkey_to_u128_chunks(&order_account.user);
+
+    let fill_receipt = &mut ctx.accounts.fill_receipt;
+    fill_receipt.batch_id = batch_id;
+    fill_receipt.order_id = order_id;
+    fill_receipt.user = order_account.user;
+    fill_receipt.enc_pubkey = order_account.enc_pubkey;
+    fill_receipt.is_ready = false;
+    fill_receipt.bump = ctx.bumps.fill_receipt;
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let args = vec![
+        // Shared - the order owner's x25519 key from submission
+        Argument::ArcisPubkey(order_account.enc_pubkey),
+        Argument::PlaintextU128(fills_nonce),
+        // Enc<Mxe, MatchResult> stored by the match_orders callback
+        Argument::PlaintextU128(match_batch.match_nonce),
+        Argument::Account(
+            match_batch.key(),
+            MatchBatch::MATCH_DATA_OFFSET,
+            MatchBatch::MATCH_DATA_LEN,
+        ),
+        Argument::PlaintextU64(order_id),
+        Argument::PlaintextU128(owner_chunks[0]),
+        Argument::PlaintextU128(owner_chunks[1]),
+    ];
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![RevealFillsCallback::callback_ix(&[CallbackAccount {
+            pubkey: ctx.accounts.fill_receipt.key(),
+            is_writable: true,
+        }])],
+    )?;
+
+    msg!("Fill reveal queued for order {} in batch {}", order_id, batch_id);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("reveal_fills", user)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, batch_id: u64, order_id: u64)]
+pub struct RevealFills<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_FILLS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        seeds = [MATCH_BATCH_SEED, batch_id.to_le_bytes().as_ref()],
+        bump = match_batch.bump,
+    )]
+    pub match_batch: Box<Account<'info, MatchBatch>>,
+
+    #[account(
+        seeds = [
+            b"order",
+            order_id.to_le_bytes().as_ref(),
+            user.key().as_ref(),
+        ],
+        bump = order_account.bump,
+        has_one = user @ ErrorCode::NotOrderOwner,
+    )]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + FillReceipt::INIT_SPACE,
+        seeds = [
+            FILL_RECEIPT_SEED,
+            batch_id.to_le_bytes().as_ref(),
+            order_id.to_le_bytes().as_ref(),
+            user.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub fill_receipt: Box<Account<'info, FillReceipt>>,
+}
@@ -0,0 +1,108 @@
+use crate::errors::ErrorCode;
+use crate::states::*;
+use crate::RevealSettlementCallback;
+use crate::SignerAccount;
+use crate::COMP_DEF_OFFSET_REVEAL_SETTLEMENT;
+use anchor_lang::prelude::*;
+use arcium_anchor::prelude::*;
+use arcium_client::idl::arcium::types::CallbackAccount;
+
+const ORDERBOOK_SEED: &[u8] = b"order_book_state";
+const MATCH_BATCH_SEED: &[u8] = b"match_batch";
+
+use crate::ID;
+use crate::ID_CONST;
+
+// Permissionless: the revealed matches come straight from the batch's stored
+// MPC output, so whoever cranks this cannot change what gets settled.
+pub fn reveal_settlement(
+    ctx: Context<RevealSettlement>,
+    computation_offset: u64,
+    batch_id: u64,
+) -> Result<()> {
+    let match_batch = &ctx.accounts.match_batch;
+
+    require!(
+        match_batch.settlement_mode == 1,
+        ErrorCode::OnChainSettlementDisabled
+    );
+    require!(match_batch.is_complete, ErrorCode::MatchBatchNotComplete);
+    require!(!match_batch.settlement_ready, ErrorCode::SettlementAlreadyRevealed);
+
+    ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+    let args = vec![
+        // Enc<Mxe, MatchResult> stored by the match_orders callback
+        Argument::PlaintextU128(ctx.accounts.match_batch.match_nonce),
+        Argument::Account(
+            ctx.accounts.match_batch.key(),
+            MatchBatch::MATCH_DATA_OFFSET,
+            MatchBatch::MATCH_DATA_LEN,
+        ),
+    ];
+
+    queue_computation(
+        ctx.accounts,
+        computation_offset,
+        args,
+        None,
+        vec![RevealSettlementCallback::callback_ix(&[CallbackAccount {
+            pubkey: ctx.accounts.match_batch.key(),
+            is_writable: true,
+        }])],
+    )?;
+
+    msg!("Settlement reveal queued for batch {}", batch_id);
+
+    Ok(())
+}
+
+#[queue_computation_accounts("reveal_settlement", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, batch_id: u64)]
+pub struct RevealSettlement<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, SignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!())]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!())]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_SETTLEMENT))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Account<'info, ClockAccount>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+
+    #[account(
+        seeds = [ORDERBOOK_SEED],
+        bump = orderbook_state.bump,
+    )]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(
+        mut,
+        seeds = [MATCH_BATCH_SEED, batch_id.to_le_bytes().as_ref()],
+        bump = match_batch.bump,
+    )]
+    pub match_batch: Box<Account<'info, MatchBatch>>,
+}
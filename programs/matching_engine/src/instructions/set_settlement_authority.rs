@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::states::OrderBookState;
+
+const ORDER_BOOK_STATE_SEED: &[u8] = b"order_book_state";
+
+// Rotates the key allowed to settle. A multisig PDA can be set here when
+// settlements should need several signers.
+pub fn set_settlement_authority(ctx: Context<SetSettlementAuthority>, new_settlement_authority: Pubkey) -> Result<()> {
+    let orderbook_state = &mut ctx.accounts.orderbook_state;
+    let previous = orderbook_state.settlement_authority;
+    orderbook_state.settlement_authority = new_settlement_authority;
+
+    emit!(SettlementAuthorityChangedEvent {
+        previous,
+        new: new_settlement_authority,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Settlement authority changed from {} to {}", previous, new_settlement_authority);
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetSettlementAuthority<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump = orderbook_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+}
+
+#[event]
+pub struct SettlementAuthorityChangedEvent {
+    pub previous: Pubkey,
+    pub new: Pubkey,
+    pub timestamp: i64,
+}
@@ -0,0 +1,78 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::instructions::{SettlementAccounts, SettlementExecutedEvent};
+use crate::states::*;
+
+const MATCH_BATCH_SEED: &[u8] = b"match_batch";
+
+// Permissionless on-chain settlement of one revealed match. The orders and
+// vaults passed in must be the ones the MPC matched; amounts are never taken
+// from the caller.
+pub fn settle_match(
+    ctx: Context<SettleMatch>,
+    _batch_id: u64,
+    index: u8,
+) -> Result<()> {
+    let match_batch = &mut ctx.accounts.match_batch;
+    let settlement = &mut ctx.accounts.settlement;
+    let i = index as usize;
+
+    require!(
+        match_batch.settlement_mode == 1,
+        ErrorCode::OnChainSettlementDisabled
+    );
+    require!(match_batch.settlement_ready, ErrorCode::SettlementNotRevealed);
+    require!(index < match_batch.num_settlements, ErrorCode::InvalidSettlementOrder);
+
+    let entry = match_batch.settlements[i];
+    require!(!entry.is_settled, ErrorCode::AlreadySettled);
+    require!(
+        entry.buyer_order_id == settlement.buyer_order.order_id
+            && entry.buyer == settlement.buyer_order.user
+            && entry.seller_order_id == settlement.seller_order.order_id
+            && entry.seller == settlement.seller_order.user,
+        ErrorCode::InvalidSettlementOrder
+    );
+
+    let quote_amount = entry
+        .quantity
+        .checked_mul(entry.execution_price)
+        .ok_or(ErrorCode::Overflow)?;
+
+    settlement.settle(
+        ctx.bumps.settlement.vault_authority,
+        entry.quantity,
+        quote_amount,
+        entry.buyer_filled,
+        entry.seller_filled,
+        entry.buyer_owed,
+        entry.seller_owed,
+    )?;
+
+    match_batch.settlements[i].is_settled = true;
+
+    emit!(SettlementExecutedEvent {
+        match_id: entry.match_id,
+        buyer: entry.buyer,
+        seller: entry.seller,
+        quantity: entry.quantity,
+        execution_price: entry.execution_price,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(batch_id: u64)]
+pub struct SettleMatch<'info> {
+    pub payer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [MATCH_BATCH_SEED, batch_id.to_le_bytes().as_ref()],
+        bump = match_batch.bump,
+    )]
+    pub match_batch: Box<Account<'info, MatchBatch>>,
+
+    pub settlement: SettlementAccounts<'info>,
+}
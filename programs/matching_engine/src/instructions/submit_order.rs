@@ -13,55 +13,84 @@ use arcium_client::idl::arcium::types::CallbackAccount;
 const VAULT_SEED: &[u8] = b"vault";
 const VAULT_STATE_SEED: &[u8] = b"vault_state";
 const ORDERBOOK_SEED: &[u8] = b"order_book_state";
+const BOOK_PAGE_SEED: &[u8] = b"order_book_page";
 
 use crate::ID;
 use crate::ID_CONST;
 
-fn pubkey_to_u64_chunks(pubkey_bytes: &[u8; 32]) -> [u64; 4] {
+// Arcis has no pubkey type, so owners travel through the circuits as two u128
+// halves: 2 ciphertexts per order instead of 32 for a byte array.
+pub fn pubkey_to_u128_chunks(pubkey: &Pubkey) -> [u128; 2] {
+    let bytes = pubkey.to_bytes();
     [
-        u64::from_le_bytes(pubkey_bytes[0..8].try_into().unwrap()),
-        u64::from_le_bytes(pubkey_bytes[8..16].try_into().unwrap()),
-        u64::from_le_bytes(pubkey_bytes[16..24].try_into().unwrap()),
-        u64::from_le_bytes(pubkey_bytes[24..32].try_into().unwrap()),
+        u128::from_le_bytes(bytes[0..16].try_into().unwrap()),
+        u128::from_le_bytes(bytes[16..32].try_into().unwrap()),
     ]
 }
 
+pub fn u128_chunks_to_pubkey(chunks: &[u128; 2]) -> Pubkey {
+    let mut bytes = [0u8; 32];
+    bytes[0..16].copy_from_slice(&chunks[0].to_le_bytes());
+    bytes[16..32].copy_from_slice(&chunks[1].to_le_bytes());
+    Pubkey::new_from_array(bytes)
+}
+
 pub fn submit_order(
     ctx: Context<SubmitOrder>,
     amount: [u8; 32],
     price: [u8; 32],
+    display_amount: [u8; 32],
+    trigger_price: [u8; 32],
+    min_fill: [u8; 32],
     user_pubkey: [u8; 32],
     order_type: u8, // 0 = buy, 1 = sell
+    order_kind: u8, // 0 = limit, 1 = market; a market order's price is its protection limit
+    time_in_force: u8, // 0 = GTC, 1 = IOC, 2 = FOK
+    post_only: u8, // 0 = off, 1 = reject if crossing, 2 = reprice one tick away
+    is_stop: u8, // 0 = live order, 1 = held until the last execution price reaches trigger_price
+    peg: u8, // 0 = none, 1 = midpoint; the price is then the peg's hidden limit (0 = uncapped)
+    expiry: i64, // unix time after which the order is evicted, 0 = good till cancelled
+    locked_amount: u64, // funds reserved for the order; must cover quantity (sell) or quantity * limit (buy)
     computation_offset: u64,
     order_id: u64,
     order_nonce: u128,
 ) -> Result<()> {
-    // msg!("Program-side signer key received: {}", ctx.accounts.user.key());
-    // let locked_amount = if order_type == 0 {
-    //     u64::from_le_bytes(amount).checked_mul(u64::from_le_bytes(price)).ok_or(ErrorCode::Overflow)?
-    // } else {
-    //     u64::from_le_bytes(amount)
-    // };
-
-    // // Check vault has sufficient funds
-    // let vault = &ctx.accounts.vault;
-
-    // let locked_total = ctx.accounts.vault_state.locked_amount;
+    require!(order_type <= 1, ErrorCode::InvalidOrderType);
+    require!(order_kind <= 1, ErrorCode::InvalidOrderKind);
+    require!(time_in_force <= 2, ErrorCode::InvalidTimeInForce);
+    require!(post_only <= 2, ErrorCode::InvalidPostOnly);
+    // Only a resting limit order can be a maker
+    require!(
+        post_only == 0 || (order_kind == 0 && time_in_force == 0),
+        ErrorCode::PostOnlyRequiresRestingLimit
+    );
+    require!(is_stop <= 1, ErrorCode::InvalidStopOrder);
+    require!(is_stop == 0 || post_only == 0, ErrorCode::InvalidStopOrder);
+    require!(peg <= 1, ErrorCode::InvalidPeg);
+    require!(
+        peg == 0 || (order_kind == 0 && post_only == 0 && is_stop == 0),
+        ErrorCode::InvalidPeg
+    );
+    require!(
+        expiry == 0 || expiry > Clock::get()?.unix_timestamp,
+        ErrorCode::InvalidExpiry
+    );
+    require!(ctx.accounts.book_page.is_ready, ErrorCode::BookPageNotReady);
 
-    // let available = vault
-    //     .amount
-    //     .checked_sub(locked_total)
-    //     .ok_or(ErrorCode::InsufficientBalance)?;
-
-    // msg!("available: {}", available);
-    // msg!("locked_amount: {}", locked_amount);
-
-    // require!(available >= locked_amount, ErrorCode::InsufficientBalance);
+    // Amount and price are ciphertexts here, so the user commits a plaintext
+    // bound that is reserved now and the circuit checks the order against it.
+    let available = ctx
+        .accounts
+        .vault
+        .amount
+        .checked_sub(ctx.accounts.vault_state.locked_amount)
+        .ok_or(ErrorCode::InsufficientBalance)?;
+    require!(available >= locked_amount, ErrorCode::InsufficientBalance);
 
 
     // msg!("=== submitOrder Accounts ===");
     // msg!("User: {}", ctx.accounts.mxe_account.key());
-    // msg!("Vault PDA: {}", ctx.accounts.base_mint.key());
+    // msg!("Vault PDA: {}", ctx.accounts.mint.key());
     // msg!("Vault State PDA: {}", ctx.accounts.vault_state.key());
     // msg!("Order Account PDA: {}", ctx.accounts.order_account.key());
     // msg!("Orderbook PDA: {}", ctx.accounts.orderbook_state.key());
@@ -71,13 +100,32 @@ pub fn submit_order(
     let order_account = &mut ctx.accounts.order_account;
     order_account.order_id = order_id;
     order_account.user = ctx.accounts.user.key();
+    order_account.enc_pubkey = user_pubkey;
     order_account.order_type = order_type;
+    order_account.order_kind = order_kind;
+    order_account.time_in_force = time_in_force;
+    order_account.post_only = post_only;
+    order_account.is_stop = is_stop;
+    order_account.peg = peg;
+    order_account.page = ctx.accounts.book_page.page_index;
+    order_account.expiry = expiry;
     order_account.status = 0; // Pending
+    order_account.locked_amount = locked_amount;
     order_account.filled_amount = 0;
+    order_account.spent_amount = 0;
     order_account.timestamp = Clock::get()?.unix_timestamp;
+    order_account.last_amended = 0;
+    order_account.amend_locked_total = 0;
+    order_account.amend_reserved = 0;
     order_account.bump = ctx.bumps.order_account;
 
     // Update vault state
+    ctx.accounts.vault_state.locked_amount = ctx
+        .accounts
+        .vault_state
+        .locked_amount
+        .checked_add(locked_amount)
+        .ok_or(ErrorCode::Overflow)?;
     ctx.accounts.vault_state.num_active_orders = ctx
         .accounts
         .vault_state
@@ -85,11 +133,16 @@ pub fn submit_order(
         .checked_add(1)
         .ok_or(ErrorCode::Overflow)?;
 
-    // Get user pubkey as bytes
-    let user_pubkey_bytes = ctx.accounts.user.key().to_bytes();
-    msg!("user_pubkey_bytes: {:?}", user_pubkey_bytes);
-    let user_chunks = pubkey_to_u64_chunks(&user_pubkey_bytes);
+    // Owner identity carried inside the encrypted order
+    let user_chunks = pubkey_to_u128_chunks(&ctx.accounts.user.key());
+
 
+    // Nothing else may be queued on the book or the page until the callback
+    // has written them back
+    let computation = ctx.accounts.computation_account.key();
+    let now = Clock::get()?.unix_timestamp;
+    ctx.accounts.orderbook_state.begin_computation(computation, now)?;
+    ctx.accounts.book_page.begin_computation(computation, now)?;
 
     ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
     
@@ -98,23 +151,41 @@ pub fn submit_order(
         Argument::PlaintextU128(order_nonce),
         Argument::EncryptedU64(amount), // Client encrypts this
         Argument::EncryptedU64(price),  // Client encrypts this
+        Argument::EncryptedU64(display_amount), // Iceberg slice, 0 for a plain order
+        Argument::EncryptedU64(trigger_price),  // Stop trigger, ignored unless is_stop
+        Argument::EncryptedU64(min_fill),       // Smallest acceptable fill, 0 = any
         // Enc<Mxe, OrderBook>
 
         Argument::PlaintextU128(ctx.accounts.orderbook_state.orderbook_nonce),
         Argument::Account(
             ctx.accounts.orderbook_state.key(),
-            8 + 32,      // Offset: discriminator(8) + authority(32) = 40
-            52 * 32,     // Size: 41 chunks × 32 bytes = 1312 bytes
+            OrderBookState::ORDERBOOK_OFFSET,
+            OrderBookState::ORDERBOOK_LEN,
+        ),
+        // Enc<Mxe, BookPage> - the order's home page, used once the top of
+        // the book is full
+        Argument::PlaintextU128(ctx.accounts.book_page.page_nonce),
+        Argument::Account(
+            ctx.accounts.book_page.key(),
+            OrderBookPage::PAGE_OFFSET,
+            OrderBookPage::PAGE_LEN,
         ),
 
         Argument::PlaintextU64(order_id),
-        // // Pass [u8; 32] as 4x u64 chunks
-        // Argument::PlaintextU64(user_chunks[0]),
-        // Argument::PlaintextU64(user_chunks[1]),
-        // Argument::PlaintextU64(user_chunks[2]),
-        // Argument::PlaintextU64(user_chunks[3]),
+        // Pass [u8; 32] as 2x u128 chunks
+        Argument::PlaintextU128(user_chunks[0]),
+        Argument::PlaintextU128(user_chunks[1]),
         Argument::PlaintextU8(order_type),
+        Argument::PlaintextU8(order_kind),
+        Argument::PlaintextU8(time_in_force),
+        Argument::PlaintextU8(post_only),
+        Argument::PlaintextU8(is_stop),
+        Argument::PlaintextU8(peg),
+        Argument::PlaintextU64(expiry as u64),
+        Argument::PlaintextU64(ctx.accounts.orderbook_state.config.tick_size),
         Argument::PlaintextU64(Clock::get()?.unix_timestamp as u64),
+        Argument::PlaintextU64(locked_amount),
+        Argument::PlaintextU8(ctx.accounts.book_page.page_index),
     ];
 
     queue_computation(
@@ -123,14 +194,26 @@ pub fn submit_order(
         args,
         None,
         vec![SubmitOrderCallback::callback_ix(&[
+            CallbackAccount {
+                pubkey: ctx.accounts.order_account.key(),
+                is_writable: true,
+            },
             CallbackAccount {
                 pubkey: ctx.accounts.orderbook_state.key(),
                 is_writable: true,
             },
             CallbackAccount {
-                pubkey: ctx.accounts.order_account.key(),
+                pubkey: ctx.accounts.vault_state.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.book_page.key(),
                 is_writable: true,
             },
+            CallbackAccount {
+                pubkey: ctx.accounts.computation_account.key(),
+                is_writable: false,
+            },
         ])],
     )?;
 
@@ -145,8 +228,18 @@ pub fn submit_order(
 #[instruction(
     amount: [u8; 32],
     price: [u8; 32],
+    display_amount: [u8; 32],
+    trigger_price: [u8; 32],
+    min_fill: [u8; 32],
     user_pubkey: [u8; 32],
     order_type: u8,
+    order_kind: u8,
+    time_in_force: u8,
+    post_only: u8,
+    is_stop: u8,
+    peg: u8,
+    expiry: i64,
+    locked_amount: u64,
     computation_offset: u64,
     order_id: u64,
     order_nonce: u128,
@@ -185,18 +278,19 @@ pub struct SubmitOrder<'info> {
     pub system_program: Program<'info, System>, //
     pub arcium_program: Program<'info, Arcium>, //
 
+    // Collateral mint: the quote mint for a buy, the base mint for a sell
     #[account(mut)]
-    pub base_mint: InterfaceAccount<'info, Mint>,
+    pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
-        seeds = [VAULT_SEED, base_mint.key().as_ref(), user.key().as_ref()],
+        seeds = [VAULT_SEED, mint.key().as_ref(), user.key().as_ref()],
         bump,
     )]
     pub vault: Account<'info, TokenAccount>,
 
     #[account(
-        init_if_needed,
+        init,
         payer = user,
         space = 8 + OrderAccount::INIT_SPACE,
         seeds = [
@@ -209,7 +303,7 @@ pub struct SubmitOrder<'info> {
     pub order_account: Box<Account<'info, OrderAccount>>,
     #[account(
         mut,
-        seeds = [VAULT_STATE_SEED, base_mint.key().as_ref(), user.key().as_ref()],
+        seeds = [VAULT_STATE_SEED, mint.key().as_ref(), user.key().as_ref()],
         bump = vault_state.bump,
     )]
     pub vault_state: Box<Account<'info, VaultState>>,
@@ -218,8 +312,17 @@ pub struct SubmitOrder<'info> {
         mut,
         seeds = [ORDERBOOK_SEED],
         bump = orderbook_state.bump,
+        constraint = mint.key() == orderbook_state.collateral_mint(order_type) @ ErrorCode::InvalidCollateralMint,
     )]
     pub orderbook_state: Box<Account<'info, OrderBookState>>,
+
+    // Any page with room on the order's side; it becomes the order's home
+    #[account(
+        mut,
+        seeds = [BOOK_PAGE_SEED, &[book_page.page_index]],
+        bump = book_page.bump,
+    )]
+    pub book_page: Box<Account<'info, OrderBookPage>>,
 }
 
 #[event]
@@ -9,13 +9,21 @@ use crate::MatchOrdersCallback;
 use arcium_client::idl::arcium::types::CallbackAccount;
 
 const ORDERBOOK_SEED: &[u8] = b"order_book_state";
+const MATCH_BATCH_SEED: &[u8] = b"match_batch";
+const BOOK_PAGE_SEED: &[u8] = b"order_book_page";
 use crate::ID;
 use crate::ID_CONST;
 
+// `page_index` picks the page whose orders the run pulls up into the top of
+// the book, before matching and into the room its fills free; cranks usually
+// pick the fullest page, whose counts are public.
 pub fn trigger_matching(
     ctx: Context<TriggerMatching>,
     computation_offset: u64,
+    page_index: u8,
 ) -> Result<()> {
+    require!(ctx.accounts.book_page.is_ready, ErrorCode::BookPageNotReady);
+
     let orderbook_state = &mut ctx.accounts.orderbook_state;
     let current_time = Clock::get()?.unix_timestamp;
     
@@ -25,28 +33,85 @@ pub fn trigger_matching(
         ErrorCode::MatchingTooFrequent
     );
 
+    // Nothing else may be queued on the book or the page until the callback
+    // has written them back
+    let computation = ctx.accounts.computation_account.key();
+    orderbook_state.begin_computation(computation, current_time)?;
+    ctx.accounts.book_page.begin_computation(computation, current_time)?;
+
     orderbook_state.last_match_timestamp = current_time;
 
+    // Match results and evictions of this run land in a fresh per-batch account
+    let match_batch = &mut ctx.accounts.match_batch;
+    match_batch.batch_id = orderbook_state.batch_count;
+    match_batch.match_nonce = 0;
+    match_batch.result_nonce = 0;
+    match_batch.num_evictions = 0;
+    match_batch.is_complete = false;
+    match_batch.tape_published = false;
+    match_batch.settlement_ready = false;
+    match_batch.num_settlements = 0;
+    match_batch.timestamp = current_time;
+    match_batch.trade_tape = orderbook_state.config.trade_tape;
+    match_batch.settlement_mode = orderbook_state.config.settlement_mode;
+    match_batch.bump = ctx.bumps.match_batch;
+    orderbook_state.batch_count = orderbook_state.batch_count
+        .checked_add(1)
+        .ok_or(ErrorCode::Overflow)?;
+
     let args = vec![
         // Backend user (Shared) - To receive encrypted match results
         Argument::ArcisPubkey(orderbook_state.backend_pubkey),
         Argument::PlaintextU128(0),  // Fresh nonce for this match batch (generated by MXE)
-        
+        // Mxe - stored copy of the batch result for per-order fill reveals
+        Argument::PlaintextU128(0),
+
         // OrderBook (Enc<Mxe, OrderBook>)
         Argument::PlaintextU128(orderbook_state.orderbook_nonce),
         Argument::Account(
             orderbook_state.key(),
-            8 + 32,  
-            52 * 32, 
+            OrderBookState::ORDERBOOK_OFFSET,
+            OrderBookState::ORDERBOOK_LEN,
         ),
+        // Enc<Mxe, BookPage> - the page this run pulls orders up from
+        Argument::PlaintextU128(ctx.accounts.book_page.page_nonce),
+        Argument::Account(
+            ctx.accounts.book_page.key(),
+            OrderBookPage::PAGE_OFFSET,
+            OrderBookPage::PAGE_LEN,
+        ),
+        Argument::PlaintextU8(page_index),
+        // Current time: expires good-till-time orders and stamps iceberg
+        // slices refilled in this run
+        Argument::PlaintextU64(current_time as u64),
+        Argument::PlaintextU8(orderbook_state.config.self_trade_policy),
+        Argument::PlaintextU8(orderbook_state.config.price_rule),
+        Argument::PlaintextU64(orderbook_state.config.tick_size),
+        Argument::PlaintextU8(orderbook_state.config.matching_mode),
+        Argument::PlaintextU8(orderbook_state.config.allocation),
+        Argument::PlaintextU64(orderbook_state.config.min_allocation),
     ];
 
     ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
 
-    let callback_accounts = vec![CallbackAccount {
-        pubkey: ctx.accounts.orderbook_state.key(),
-        is_writable: true,
-    }];
+    let callback_accounts = vec![
+        CallbackAccount {
+            pubkey: ctx.accounts.orderbook_state.key(),
+            is_writable: true,
+        },
+        CallbackAccount {
+            pubkey: ctx.accounts.match_batch.key(),
+            is_writable: true,
+        },
+        CallbackAccount {
+            pubkey: ctx.accounts.book_page.key(),
+            is_writable: true,
+        },
+        CallbackAccount {
+            pubkey: ctx.accounts.computation_account.key(),
+            is_writable: false,
+        },
+    ];
 
     queue_computation(
         ctx.accounts,
@@ -63,7 +128,7 @@ pub fn trigger_matching(
 
 #[queue_computation_accounts("match_orders", payer)]
 #[derive(Accounts)]
-#[instruction(computation_offset: u64)]
+#[instruction(computation_offset: u64, page_index: u8)]
 pub struct TriggerMatching<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -103,6 +168,20 @@ pub struct TriggerMatching<'info> {
         bump = orderbook_state.bump,
     )]
     pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + MatchBatch::INIT_SPACE,
+        seeds = [MATCH_BATCH_SEED, orderbook_state.batch_count.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub match_batch: Box<Account<'info, MatchBatch>>,
+    #[account(
+        mut,
+        seeds = [BOOK_PAGE_SEED, &[page_index]],
+        bump = book_page.bump,
+    )]
+    pub book_page: Box<Account<'info, OrderBookPage>>,
 }
 
 #[event]
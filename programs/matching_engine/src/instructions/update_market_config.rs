@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::states::{MarketConfig, OrderBookState};
+
+const ORDER_BOOK_STATE_SEED: &[u8] = b"order_book_state";
+
+pub fn update_market_config(ctx: Context<UpdateMarketConfig>, config: MarketConfig) -> Result<()> {
+    config.validate()?;
+
+    ctx.accounts.orderbook_state.config = config;
+
+    msg!(
+        "Market config updated, tick size {}, self-trade policy {}, price rule {}, matching mode {}, allocation {}, trade tape {}, settlement mode {}",
+        config.tick_size,
+        config.self_trade_policy,
+        config.price_rule,
+        config.matching_mode,
+        config.allocation,
+        config.trade_tape,
+        config.settlement_mode
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateMarketConfig<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [ORDER_BOOK_STATE_SEED],
+        bump = orderbook_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+}
@@ -24,8 +24,12 @@ pub fn withdraw_from_vault(
         to: ctx.accounts.user_token_account.to_account_info(),
         authority: ctx.accounts.vault_authority.to_account_info(),
     };
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"vault_authority",
+        &[ctx.bumps.vault_authority],
+    ]];
     let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
     token::transfer(cpi_ctx, amount)?;
 
     emit!(WithdrawEvent {
@@ -46,7 +50,7 @@ pub struct WithdrawFromVault<'info> {
     pub user_token_account: Account<'info, TokenAccount>,
     #[account(
         mut,
-        seeds = [VAULT_SEED, vault.mint.as_ref()],
+        seeds = [VAULT_SEED, vault.mint.as_ref(), user.key().as_ref()],
         bump,
     )]
     pub vault: Account<'info, TokenAccount>,
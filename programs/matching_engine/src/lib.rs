@@ -5,6 +5,14 @@ use arcium_client::idl::arcium::types::CallbackAccount;
 const COMP_DEF_OFFSET_MATCH_ORDERS: u32 = comp_def_offset("match_orders");
 const COMP_DEF_OFFSET_SUBMIT_ORDER: u32 = comp_def_offset("submit_order");
 const COMP_DEF_OFFSET_INIT_ORDER_BOOK: u32 = comp_def_offset("init_order_book");
+const COMP_DEF_OFFSET_CANCEL_ORDER: u32 = comp_def_offset("cancel_order");
+const COMP_DEF_OFFSET_AMEND_ORDER: u32 = comp_def_offset("amend_order");
+const COMP_DEF_OFFSET_REVEAL_FILLS: u32 = comp_def_offset("reveal_fills");
+const COMP_DEF_OFFSET_REVEAL_TAPE: u32 = comp_def_offset("reveal_tape");
+const COMP_DEF_OFFSET_REVEAL_SETTLEMENT: u32 = comp_def_offset("reveal_settlement");
+const COMP_DEF_OFFSET_VERIFY_SETTLEMENT: u32 = comp_def_offset("verify_settlement");
+const COMP_DEF_OFFSET_INIT_BOOK_PAGE: u32 = comp_def_offset("init_book_page");
+const COMP_DEF_OFFSET_REBALANCE_BOOK: u32 = comp_def_offset("rebalance_book");
 
 declare_id!("DQ5MR2aPD9sPBN9ukVkhwrAn8ADxpkAE5AHUnXxKEvn1");
 
@@ -34,13 +42,63 @@ pub mod matching_engine {
         init_comp_def(ctx.accounts, true, 0, None, None)?;
         Ok(())
     }
+
+    pub fn init_cancel_order_comp_def(ctx: Context<InitCancelOrderCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_amend_order_comp_def(ctx: Context<InitAmendOrderCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_reveal_fills_comp_def(ctx: Context<InitRevealFillsCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_reveal_tape_comp_def(ctx: Context<InitRevealTapeCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_reveal_settlement_comp_def(ctx: Context<InitRevealSettlementCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_verify_settlement_comp_def(ctx: Context<InitVerifySettlementCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_book_page_comp_def(ctx: Context<InitBookPageCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_rebalance_book_comp_def(ctx: Context<InitRebalanceBookCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, true, 0, None, None)?;
+        Ok(())
+    }
     pub fn initialize_vault(ctx: Context<InitializeUserVault>) -> Result<()> {
         instructions::initialize_user_vault(ctx)?;
         Ok(())
     }
 
-    pub fn initialize(ctx: Context<Initialize>, backend_pubkey: [u8; 32], base_mint: Pubkey, quote_mint: Pubkey) -> Result<()> {
-        instructions::initialize(ctx, backend_pubkey, base_mint, quote_mint)?;
+    pub fn initialize(ctx: Context<Initialize>, backend_pubkey: [u8; 32], base_mint: Pubkey, quote_mint: Pubkey, config: MarketConfig, settlement_authority: Pubkey) -> Result<()> {
+        instructions::initialize(ctx, backend_pubkey, base_mint, quote_mint, config, settlement_authority)?;
+        Ok(())
+    }
+
+    pub fn update_market_config(ctx: Context<UpdateMarketConfig>, config: MarketConfig) -> Result<()> {
+        instructions::update_market_config(ctx, config)?;
+        Ok(())
+    }
+
+    pub fn set_settlement_authority(ctx: Context<SetSettlementAuthority>, new_settlement_authority: Pubkey) -> Result<()> {
+        instructions::set_settlement_authority(ctx, new_settlement_authority)?;
         Ok(())
     }
 
@@ -111,21 +169,99 @@ pub mod matching_engine {
         Ok(())
     }
 
+    pub fn init_book_page(ctx: Context<InitBookPage>, computation_offset: u64, page_index: u8) -> Result<()> {
+        instructions::init_book_page(ctx, computation_offset, page_index)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "init_book_page", network = "localnet")]
+    pub fn init_book_page_callback(
+        ctx: Context<InitBookPageCallback>,
+        output: ComputationOutputs<InitBookPageOutput>,
+    ) -> Result<()> {
+        let page = match &output {
+            ComputationOutputs::Success(InitBookPageOutput { field_0: page }) => page,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        let book_page = &mut ctx.accounts.book_page;
+        book_page.page_nonce = page.nonce;
+        book_page.page_data = page.ciphertexts;
+        book_page.is_ready = true;
+
+        msg!("Book page {} initialized", book_page.page_index);
+        Ok(())
+    }
+
+    pub fn rebalance_book(ctx: Context<RebalanceBook>, computation_offset: u64, page_index: u8) -> Result<()> {
+        instructions::rebalance_book(ctx, computation_offset, page_index)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "rebalance_book", network = "localnet")]
+    pub fn rebalance_book_callback(
+        ctx: Context<RebalanceBookCallback>,
+        output: ComputationOutputs<RebalanceBookOutput>,
+    ) -> Result<()> {
+        // Only the computation still holding the book and the page may write
+        // them back; a failed or superseded one just gives them up
+        let computation = ctx.accounts.computation_account.key();
+        let holds_book = ctx.accounts.orderbook_state.end_computation(computation);
+        let holds_page = ctx.accounts.book_page.end_computation(computation);
+        let (orderbook_encrypted, page_encrypted, buy_count, sell_count, page_buy_count, page_sell_count) = match &output {
+            ComputationOutputs::Success(RebalanceBookOutput {
+                field_0: RebalanceBookOutputStruct0 {
+                    field_0: orderbook,
+                    field_1: page,
+                    field_2: buy_count,
+                    field_3: sell_count,
+                    field_4: page_buy_count,
+                    field_5: page_sell_count,
+                },
+            }) if holds_book && holds_page => (orderbook, page, *buy_count, *sell_count, *page_buy_count, *page_sell_count),
+            _ => {
+                msg!("Rebalance of book page {} discarded", ctx.accounts.book_page.page_index);
+                return Ok(());
+            }
+        };
+
+        let orderbook_state = &mut ctx.accounts.orderbook_state;
+        orderbook_state.orderbook_nonce = orderbook_encrypted.nonce;
+        orderbook_state.orderbook_data = orderbook_encrypted.ciphertexts;
+
+        let book_page = &mut ctx.accounts.book_page;
+        book_page.page_nonce = page_encrypted.nonce;
+        book_page.page_data = page_encrypted.ciphertexts;
+        book_page.buy_count = page_buy_count;
+        book_page.sell_count = page_sell_count;
+
+        emit!(BookRebalancedEvent {
+            page_index: book_page.page_index,
+            buy_count,
+            sell_count,
+            page_buy_count,
+            page_sell_count,
+        });
+
+        Ok(())
+    }
+
     pub fn deposit_to_vault(ctx: Context<DepositToVault>, amount: u64) -> Result<()> {
         instructions::deposit_to_vault(ctx, amount)?;
         Ok(())
     }
 
-    pub fn submit_order(ctx: Context<SubmitOrder>, amount: [u8;32], price: [u8;32],user_pubkey: [u8; 32], order_type: u8, computation_offset: u64, order_id: u64, order_nonce: u128) -> Result<()> {
-        instructions::submit_order(ctx, amount, price, user_pubkey, order_type, computation_offset, order_id, order_nonce)?;
+    pub fn submit_order(ctx: Context<SubmitOrder>, amount: [u8;32], price: [u8;32], display_amount: [u8;32], trigger_price: [u8;32], min_fill: [u8;32], user_pubkey: [u8; 32], order_type: u8, order_kind: u8, time_in_force: u8, post_only: u8, is_stop: u8, peg: u8, expiry: i64, locked_amount: u64, computation_offset: u64, order_id: u64, order_nonce: u128) -> Result<()> {
+        instructions::submit_order(ctx, amount, price, display_amount, trigger_price, min_fill, user_pubkey, order_type, order_kind, time_in_force, post_only, is_stop, peg, expiry, locked_amount, computation_offset, order_id, order_nonce)?;
         Ok(())
     }
 
     pub fn trigger_matching(
         ctx: Context<TriggerMatching>,
         computation_offset: u64,
+        page_index: u8,
     ) -> Result<()> {
-        instructions::trigger_matching(ctx, computation_offset)?;
+        instructions::trigger_matching(ctx, computation_offset, page_index)?;
         Ok(())
     }
 
@@ -134,19 +270,38 @@ pub mod matching_engine {
         ctx: Context<MatchOrdersCallback>,
         output: ComputationOutputs<MatchOrdersOutput>,
     ) -> Result<()> {
+    // Only the computation still holding the book and the page may write
+    // them back; a failed or superseded run leaves its batch incomplete and
+    // the orders where they were
+    let computation = ctx.accounts.computation_account.key();
+    let holds_book = ctx.accounts.orderbook_state.end_computation(computation);
+    let holds_page = ctx.accounts.book_page.end_computation(computation);
+
     // Use reference to avoid stack copies
-    let (match_result_encrypted, orderbook_encrypted) = match &output {
+    let (match_result_encrypted, orderbook_encrypted, evicted_ids, evicted_owners, eviction_reasons, evicted_owed, num_evictions, stored_result, page_encrypted, page_buy_count, page_sell_count) = match &output {
         ComputationOutputs::Success(MatchOrdersOutput {
             field_0: MatchOrdersOutputStruct0 {
                 field_0: match_result,    // Enc<Shared, MatchResult>
                 field_1: orderbook,       // Enc<Mxe, OrderBook>
+                field_2: evicted_ids,
+                field_3: evicted_owners,
+                field_4: eviction_reasons,
+                field_5: evicted_owed,
+                field_6: num_evictions,
+                field_7: stored_result,   // Enc<Mxe, MatchResult>
+                field_8: page,            // Enc<Mxe, BookPage>
+                field_9: page_buy_count,
+                field_10: page_sell_count,
             },
-        }) => (match_result, orderbook),
-        _ => return Err(ErrorCode::AbortedComputation.into()),
+        }) if holds_book && holds_page => (match_result, orderbook, evicted_ids, evicted_owners, eviction_reasons, evicted_owed, *num_evictions, stored_result, page, *page_buy_count, *page_sell_count),
+        _ => {
+            msg!("Match batch {} discarded", ctx.accounts.match_batch.batch_id);
+            return Ok(());
+        }
     };
 
-    let _match_nonce = match_result_encrypted.nonce;
-    let _match_ciphertexts = match_result_encrypted.ciphertexts;
+    let match_nonce = match_result_encrypted.nonce;
+    let match_ciphertexts = match_result_encrypted.ciphertexts;
     
     let orderbook_nonce = orderbook_encrypted.nonce;
     let orderbook_ciphertexts = orderbook_encrypted.ciphertexts;
@@ -157,14 +312,44 @@ pub mod matching_engine {
     orderbook_state.orderbook_nonce = orderbook_nonce;
     orderbook_state.orderbook_data = orderbook_ciphertexts;
     orderbook_state.total_matches = orderbook_state.total_matches.saturating_add(1);
+
+    let book_page = &mut ctx.accounts.book_page;
+    book_page.page_nonce = page_encrypted.nonce;
+    book_page.page_data = page_encrypted.ciphertexts;
+    book_page.buy_count = page_buy_count;
+    book_page.sell_count = page_sell_count;
+
+    // Record orders the circuit removed on its own so they can be finalized
+    let match_batch = &mut ctx.accounts.match_batch;
+    match_batch.result_nonce = match_nonce;
+    match_batch.result_data = match_ciphertexts;
+    match_batch.match_nonce = stored_result.nonce;
+    match_batch.match_data = stored_result.ciphertexts;
+    for i in 0..(num_evictions as usize).min(MAX_EVICTIONS_PER_BATCH) {
+        match_batch.evicted_order_ids[i] = evicted_ids[i];
+        match_batch.evicted_owners[i] = u128_chunks_to_pubkey(&evicted_owners[i]);
+        match_batch.eviction_reasons[i] = eviction_reasons[i];
+        match_batch.evicted_owed[i] = evicted_owed[i];
+        match_batch.eviction_finalized[i] = false;
+    }
+    match_batch.num_evictions = num_evictions;
+    match_batch.is_complete = true;
+
+    if num_evictions > 0 {
+        emit!(OrdersEvictedEvent {
+            batch_id: match_batch.batch_id,
+            num_evictions,
+        });
+    }
     
     // Emit event with match results
-    // emit!(MatchResultEvent {
-    //     match_ciphertexts,
-    //     match_nonce,
-    //     orderbook_nonce,
-    //     timestamp: Clock::get()?.unix_timestamp,
-    // });
+    emit!(MatchResultEvent {
+        batch_id: match_batch.batch_id,
+        match_ciphertexts,
+        match_nonce,
+        orderbook_nonce,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
     
     msg!("Matching completed. {} total matches processed.", orderbook_state.total_matches);
     
@@ -177,16 +362,46 @@ pub mod matching_engine {
         ctx: Context<SubmitOrderCallback>,
         output: ComputationOutputs<SubmitOrderOutput>,
     ) -> Result<()> {
-        let (orderbook_encrypted, success, buy_count, sell_count) = match &output {
+        // Only the computation still holding the book and the page may write
+        // them back; a failed or superseded one just gives them up
+        let computation = ctx.accounts.computation_account.key();
+        let holds_book = ctx.accounts.orderbook_state.end_computation(computation);
+        let holds_page = ctx.accounts.book_page.end_computation(computation);
+        let (orderbook_encrypted, page_encrypted, success, buy_count, sell_count, page_buy_count, page_sell_count, reject_reason) = match &output {
             ComputationOutputs::Success(SubmitOrderOutput {
                 field_0: SubmitOrderOutputStruct0 {
                     field_0: orderbook,
-                    field_1: success,
-                    field_2: buy_count,
-                    field_3: sell_count,
+                    field_1: page,
+                    field_2: success,
+                    field_3: buy_count,
+                    field_4: sell_count,
+                    field_5: page_buy_count,
+                    field_6: page_sell_count,
+                    field_7: reject_reason,
                 },
-            }) => (orderbook, *success, *buy_count, *sell_count),  // Dereference primitives
-            _ => return Err(ErrorCode::AbortedComputation.into()),
+            }) if holds_book && holds_page => (orderbook, page, *success, *buy_count, *sell_count, *page_buy_count, *page_sell_count, *reject_reason),  // Dereference primitives
+            _ => {
+                // The order never reached the book, so its funds are free again
+                let order_account = &mut ctx.accounts.order_account;
+                order_account.status = 2;
+                let vault_state = &mut ctx.accounts.vault_state;
+                vault_state.num_active_orders = vault_state.num_active_orders.saturating_sub(1);
+                vault_state.locked_amount = vault_state
+                    .locked_amount
+                    .saturating_sub(order_account.locked_amount);
+                order_account.locked_amount = 0;
+                msg!("Order {} rejected: its computation failed or was superseded", order_account.order_id);
+
+                emit!(OrderProcessedEvent {
+                    order_id: order_account.order_id,
+                    success: false,
+                    reject_reason: 4,
+                    buy_count: 0,
+                    sell_count: 0,
+                    orderbook_nonce: ctx.accounts.orderbook_state.orderbook_nonce,
+                });
+                return Ok(());
+            }
         };
     
         // Extract data
@@ -198,6 +413,12 @@ pub mod matching_engine {
         orderbook_state.orderbook_nonce = orderbook_nonce;
         orderbook_state.orderbook_data = *orderbook_ciphertexts;  // Copy array
         orderbook_state.total_orders_processed = orderbook_state.total_orders_processed.saturating_add(1);
+
+        let book_page = &mut ctx.accounts.book_page;
+        book_page.page_nonce = page_encrypted.nonce;
+        book_page.page_data = page_encrypted.ciphertexts;
+        book_page.buy_count = page_buy_count;
+        book_page.sell_count = page_sell_count;
         
         // Update order account status
         let order_account = &mut ctx.accounts.order_account;
@@ -205,15 +426,32 @@ pub mod matching_engine {
             order_account.status = 1;  // Processing (added to orderbook)
             msg!("Order {} added to orderbook. Buy count: {}, Sell count: {}", 
                  order_account.order_id, buy_count, sell_count);
+        } else if reject_reason == 2 {
+            order_account.status = 6;  // Rejected (post-only would cross)
+            msg!("Order {} rejected: post-only order would cross", order_account.order_id);
+        } else if reject_reason == 3 {
+            order_account.status = 7;  // Rejected (not covered by its locked amount)
+            msg!("Order {} rejected: not covered by its locked amount", order_account.order_id);
         } else {
-            order_account.status = 2;  // Rejected (orderbook full)
+            order_account.status = 2;  // Rejected (top of the book and its page full)
             msg!("Order {} rejected: orderbook full", order_account.order_id);
         }
+
+        // A rejected order never rests, so its funds are free again
+        if !success {
+            let vault_state = &mut ctx.accounts.vault_state;
+            vault_state.num_active_orders = vault_state.num_active_orders.saturating_sub(1);
+            vault_state.locked_amount = vault_state
+                .locked_amount
+                .saturating_sub(order_account.locked_amount);
+            order_account.locked_amount = 0;
+        }
         
         // Emit event
         emit!(OrderProcessedEvent {
             order_id: order_account.order_id,
             success,
+            reject_reason,
             buy_count,
             sell_count,
             orderbook_nonce,
@@ -221,13 +459,347 @@ pub mod matching_engine {
         
         Ok(())    
     }
+    pub fn cancel_order(ctx: Context<CancelOrder>, computation_offset: u64, order_id: u64) -> Result<()> {
+        instructions::cancel_order(ctx, computation_offset, order_id)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "cancel_order", network = "localnet")]
+    pub fn cancel_order_callback(
+        ctx: Context<CancelOrderCallback>,
+        output: ComputationOutputs<CancelOrderOutput>,
+    ) -> Result<()> {
+        // Only the computation still holding the book and the page may write
+        // them back; a failed or superseded one just gives them up
+        let computation = ctx.accounts.computation_account.key();
+        let holds_book = ctx.accounts.orderbook_state.end_computation(computation);
+        let holds_page = ctx.accounts.book_page.end_computation(computation);
+        let (orderbook_encrypted, page_encrypted, success, buy_count, sell_count, page_buy_count, page_sell_count, owed) = match &output {
+            ComputationOutputs::Success(CancelOrderOutput {
+                field_0: CancelOrderOutputStruct0 {
+                    field_0: orderbook,
+                    field_1: page,
+                    field_2: success,
+                    field_3: buy_count,
+                    field_4: sell_count,
+                    field_5: page_buy_count,
+                    field_6: page_sell_count,
+                    field_7: owed,
+                },
+            }) if holds_book && holds_page => (orderbook, page, *success, *buy_count, *sell_count, *page_buy_count, *page_sell_count, *owed),
+            _ => {
+                // The order is still in the book; the cancel can be sent again
+                msg!("Cancellation of order {} discarded", ctx.accounts.order_account.order_id);
+                return Ok(());
+            }
+        };
+
+        let orderbook_nonce = orderbook_encrypted.nonce;
+
+        let orderbook_state = &mut ctx.accounts.orderbook_state;
+        orderbook_state.orderbook_nonce = orderbook_nonce;
+        orderbook_state.orderbook_data = orderbook_encrypted.ciphertexts;
+
+        let book_page = &mut ctx.accounts.book_page;
+        book_page.page_nonce = page_encrypted.nonce;
+        book_page.page_data = page_encrypted.ciphertexts;
+        book_page.buy_count = page_buy_count;
+        book_page.sell_count = page_sell_count;
+
+        let order_account = &mut ctx.accounts.order_account;
+        if success {
+            order_account.status = 2;  // Cancelled
+            // Fills matched before the cancel still settle from the lock
+            let released = order_account.release_unowed(owed);
+            let vault_state = &mut ctx.accounts.vault_state;
+            vault_state.num_active_orders = vault_state.num_active_orders.saturating_sub(1);
+            vault_state.locked_amount = vault_state.locked_amount.saturating_sub(released);
+            msg!("Order {} cancelled. Buy count: {}, Sell count: {}",
+                 order_account.order_id, buy_count, sell_count);
+        } else {
+            // Already filled or removed by a previous batch
+            msg!("Order {} not found in orderbook", order_account.order_id);
+        }
+
+        emit!(OrderCancelledEvent {
+            order_id: order_account.order_id,
+            user: order_account.user,
+            success,
+            buy_count,
+            sell_count,
+            orderbook_nonce,
+        });
+
+        Ok(())
+    }
+
+    pub fn amend_order(ctx: Context<AmendOrder>, amount: [u8;32], price: [u8;32], display_amount: [u8;32], user_pubkey: [u8; 32], locked_amount: u64, computation_offset: u64, order_id: u64, order_nonce: u128) -> Result<()> {
+        instructions::amend_order(ctx, amount, price, display_amount, user_pubkey, locked_amount, computation_offset, order_id, order_nonce)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "amend_order", network = "localnet")]
+    pub fn amend_order_callback(
+        ctx: Context<AmendOrderCallback>,
+        output: ComputationOutputs<AmendOrderOutput>,
+    ) -> Result<()> {
+        // Only the computation still holding the book and the page may write
+        // them back; a failed or superseded one just gives them up
+        let computation = ctx.accounts.computation_account.key();
+        let holds_book = ctx.accounts.orderbook_state.end_computation(computation);
+        let holds_page = ctx.accounts.book_page.end_computation(computation);
+        let (orderbook_encrypted, page_encrypted, success) = match &output {
+            ComputationOutputs::Success(AmendOrderOutput {
+                field_0: AmendOrderOutputStruct0 {
+                    field_0: orderbook,
+                    field_1: page,
+                    field_2: success,
+                },
+            }) if holds_book && holds_page => (orderbook, page, *success),
+            _ => {
+                // The order keeps its old terms and the lock it had
+                let order_account = &mut ctx.accounts.order_account;
+                let released = order_account.finish_amendment(false);
+                let vault_state = &mut ctx.accounts.vault_state;
+                vault_state.locked_amount = vault_state.locked_amount.saturating_sub(released);
+                msg!("Amendment of order {} discarded", order_account.order_id);
+                return Ok(());
+            }
+        };
+
+        let orderbook_nonce = orderbook_encrypted.nonce;
+
+        let orderbook_state = &mut ctx.accounts.orderbook_state;
+        orderbook_state.orderbook_nonce = orderbook_nonce;
+        orderbook_state.orderbook_data = orderbook_encrypted.ciphertexts;
+
+        let book_page = &mut ctx.accounts.book_page;
+        book_page.page_nonce = page_encrypted.nonce;
+        book_page.page_data = page_encrypted.ciphertexts;
+
+        let timestamp = Clock::get()?.unix_timestamp;
+        let order_account = &mut ctx.accounts.order_account;
+        // A reduction gives back what the amended order no longer needs; a
+        // rejected amendment gives back what was reserved for an increase
+        let released = order_account.finish_amendment(success);
+        let vault_state = &mut ctx.accounts.vault_state;
+        vault_state.locked_amount = vault_state.locked_amount.saturating_sub(released);
+        if success {
+            order_account.last_amended = timestamp;
+            msg!("Order {} amended, {} locked", order_account.order_id, order_account.locked_amount);
+        } else {
+            msg!("Order {} not amended: not found, zero amount or not covered", order_account.order_id);
+        }
+
+        emit!(OrderAmendedEvent {
+            order_id: order_account.order_id,
+            user: order_account.user,
+            success,
+            locked_amount: order_account.locked_amount,
+            orderbook_nonce,
+            timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn finalize_evicted_order(ctx: Context<FinalizeEvictedOrder>, batch_id: u64, index: u8) -> Result<()> {
+        instructions::finalize_evicted_order(ctx, batch_id, index)?;
+        Ok(())
+    }
+
+    pub fn reveal_fills(ctx: Context<RevealFills>, computation_offset: u64, batch_id: u64, order_id: u64, fills_nonce: u128) -> Result<()> {
+        instructions::reveal_fills(ctx, computation_offset, batch_id, order_id, fills_nonce)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "reveal_fills", network = "localnet")]
+    pub fn reveal_fills_callback(
+        ctx: Context<RevealFillsCallback>,
+        output: ComputationOutputs<RevealFillsOutput>,
+    ) -> Result<()> {
+        let fills = match &output {
+            ComputationOutputs::Success(RevealFillsOutput { field_0: fills }) => fills,
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        // Several reveals can be in flight before the first lands; only the
+        // first one reaching the callback writes the receipt
+        let fill_receipt = &mut ctx.accounts.fill_receipt;
+        if fill_receipt.is_ready {
+            return Ok(());
+        }
+        fill_receipt.fills_nonce = fills.nonce;
+        fill_receipt.fills_data = fills.ciphertexts;
+        fill_receipt.is_ready = true;
+
+        emit!(FillsRevealedEvent {
+            batch_id: fill_receipt.batch_id,
+            order_id: fill_receipt.order_id,
+            user: fill_receipt.user,
+            nonce: fills.nonce,
+            ciphertexts: fills.ciphertexts,
+        });
+
+        Ok(())
+    }
+
+    pub fn publish_trade_tape(ctx: Context<PublishTradeTape>, computation_offset: u64, batch_id: u64) -> Result<()> {
+        instructions::publish_trade_tape(ctx, computation_offset, batch_id)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "reveal_tape", network = "localnet")]
+    pub fn reveal_tape_callback(
+        ctx: Context<RevealTapeCallback>,
+        output: ComputationOutputs<RevealTapeOutput>,
+    ) -> Result<()> {
+        let (quantities, prices, count) = match &output {
+            ComputationOutputs::Success(RevealTapeOutput {
+                field_0: RevealTapeOutputStruct0 {
+                    field_0: quantities,
+                    field_1: prices,
+                    field_2: count,
+                },
+            }) => (quantities, prices, *count),
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        // Several publish calls can be in flight before the first lands; only
+        // the first one reaching the callback writes the tape
+        let match_batch = &mut ctx.accounts.match_batch;
+        if match_batch.tape_published {
+            return Ok(());
+        }
+        match_batch.tape_published = true;
+
+        let trade_tape = &mut ctx.accounts.trade_tape;
+        for i in 0..(count as usize).min(MAX_MATCHES_PER_BATCH) {
+            trade_tape.push(TapeEntry {
+                batch_id: match_batch.batch_id,
+                quantity: quantities[i],
+                price: prices[i],
+                timestamp: match_batch.timestamp,
+            });
+        }
+
+        emit!(TradeTapeEvent {
+            batch_id: match_batch.batch_id,
+            quantities: *quantities,
+            prices: *prices,
+            count,
+            timestamp: match_batch.timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn reveal_settlement(ctx: Context<RevealSettlement>, computation_offset: u64, batch_id: u64) -> Result<()> {
+        instructions::reveal_settlement(ctx, computation_offset, batch_id)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "reveal_settlement", network = "localnet")]
+    pub fn reveal_settlement_callback(
+        ctx: Context<RevealSettlementCallback>,
+        output: ComputationOutputs<RevealSettlementOutput>,
+    ) -> Result<()> {
+        let (match_ids, buyers, sellers, buyer_order_ids, seller_order_ids, quantities, prices, buyers_filled, sellers_filled, buyers_owed, sellers_owed, count) = match &output {
+            ComputationOutputs::Success(RevealSettlementOutput {
+                field_0: RevealSettlementOutputStruct0 {
+                    field_0: match_ids,
+                    field_1: buyers,
+                    field_2: sellers,
+                    field_3: buyer_order_ids,
+                    field_4: seller_order_ids,
+                    field_5: quantities,
+                    field_6: prices,
+                    field_7: buyers_filled,
+                    field_8: sellers_filled,
+                    field_9: buyers_owed,
+                    field_10: sellers_owed,
+                    field_11: count,
+                },
+            }) => (match_ids, buyers, sellers, buyer_order_ids, seller_order_ids, quantities, prices, buyers_filled, sellers_filled, buyers_owed, sellers_owed, *count),
+            _ => return Err(ErrorCode::AbortedComputation.into()),
+        };
+
+        // A reveal landing after the first would reset settled matches
+        let match_batch = &mut ctx.accounts.match_batch;
+        if match_batch.settlement_ready {
+            return Ok(());
+        }
+
+        let num_settlements = (count as usize).min(MAX_MATCHES_PER_BATCH);
+        for i in 0..num_settlements {
+            match_batch.settlements[i] = PendingSettlement {
+                match_id: match_ids[i],
+                buyer: u128_chunks_to_pubkey(&buyers[i]),
+                seller: u128_chunks_to_pubkey(&sellers[i]),
+                buyer_order_id: buyer_order_ids[i],
+                seller_order_id: seller_order_ids[i],
+                quantity: quantities[i],
+                execution_price: prices[i],
+                buyer_filled: buyers_filled[i],
+                seller_filled: sellers_filled[i],
+                buyer_owed: buyers_owed[i],
+                seller_owed: sellers_owed[i],
+                is_settled: false,
+            };
+        }
+        match_batch.num_settlements = num_settlements as u8;
+        match_batch.settlement_ready = true;
+
+        emit!(SettlementRevealedEvent {
+            batch_id: match_batch.batch_id,
+            num_settlements: match_batch.num_settlements,
+        });
+
+        Ok(())
+    }
+
+    pub fn settle_match(ctx: Context<SettleMatch>, batch_id: u64, index: u8) -> Result<()> {
+        instructions::settle_match(ctx, batch_id, index)?;
+        Ok(())
+    }
+
     pub fn withdraw_from_vault(ctx: Context<WithdrawFromVault>, amount: u64) -> Result<()> {
         instructions::withdraw_from_vault(ctx, amount)?;
         Ok(())
     }
 
-    pub fn execute_settlement(ctx: Context<ExecuteSettlement>, match_id: u64, quantity: u64, execution_price: u64) -> Result<()> {
-        instructions::execute_settlement(ctx, match_id, quantity, execution_price)?;
+    pub fn commit_settlement(ctx: Context<CommitSettlement>, computation_offset: u64, batch_id: u64, claim: SettlementClaim) -> Result<()> {
+        instructions::commit_settlement(ctx, computation_offset, batch_id, claim)?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "verify_settlement", network = "localnet")]
+    pub fn verify_settlement_callback(
+        ctx: Context<VerifySettlementCallback>,
+        output: ComputationOutputs<VerifySettlementOutput>,
+    ) -> Result<()> {
+        // A check that did not run counts as failed, so the record is not
+        // left locked and the bot can commit again
+        let committed = match &output {
+            ComputationOutputs::Success(VerifySettlementOutput { field_0: committed }) => *committed,
+            _ => false,
+        };
+
+        let match_record = &mut ctx.accounts.match_record;
+        match_record.verification_pending = false;
+        match_record.is_verified = committed;
+
+        emit!(SettlementVerifiedEvent {
+            batch_id: match_record.batch_id,
+            match_id: match_record.match_id,
+            verified: committed,
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_settlement(ctx: Context<ExecuteSettlement>, batch_id: u64, claim: SettlementClaim) -> Result<()> {
+        instructions::execute_settlement(ctx, batch_id, claim)?;
         Ok(())
     }
 }
@@ -236,6 +808,7 @@ pub mod matching_engine {
 pub struct OrderProcessedEvent {
     pub order_id: u64,
     pub success: bool,
+    pub reject_reason: u8, // 0 = none, 1 = orderbook full, 2 = post-only would cross, 3 = not covered by the locked amount, 4 = computation failed or superseded
     pub buy_count: u8,
     pub sell_count: u8,
     pub orderbook_nonce: u128,
@@ -252,6 +825,18 @@ pub struct MatchOrdersCallback<'info> {
     pub instructions_sysvar: AccountInfo<'info>,
     #[account(mut)]
     pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(mut)]
+    pub match_batch: Box<Account<'info, MatchBatch>>,
+    #[account(mut)]
+    pub book_page: Box<Account<'info, OrderBookPage>>,
+    /// CHECK: the computation this callback answers, compared with the one holding the book
+    pub computation_account: UncheckedAccount<'info>,
+}
+
+#[event]
+pub struct OrdersEvictedEvent {
+    pub batch_id: u64,
+    pub num_evictions: u8,
 }
 
 #[callback_accounts("submit_order")]
@@ -267,6 +852,159 @@ pub struct SubmitOrderCallback<'info> {
     pub order_account: Box<Account<'info, OrderAccount>>,
     #[account(mut)]
     pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(mut)]
+    pub vault_state: Box<Account<'info, VaultState>>,
+    #[account(mut)]
+    pub book_page: Box<Account<'info, OrderBookPage>>,
+    /// CHECK: the computation this callback answers, compared with the one holding the book
+    pub computation_account: UncheckedAccount<'info>,
+}
+
+#[callback_accounts("cancel_order")]
+#[derive(Accounts)]
+pub struct CancelOrderCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CANCEL_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(mut)]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+    #[account(mut)]
+    pub vault_state: Box<Account<'info, VaultState>>,
+    #[account(mut)]
+    pub book_page: Box<Account<'info, OrderBookPage>>,
+    /// CHECK: the computation this callback answers, compared with the one holding the book
+    pub computation_account: UncheckedAccount<'info>,
+}
+
+#[event]
+pub struct OrderCancelledEvent {
+    pub order_id: u64,
+    pub user: Pubkey,
+    pub success: bool,
+    pub buy_count: u8,
+    pub sell_count: u8,
+    pub orderbook_nonce: u128,
+}
+
+#[callback_accounts("amend_order")]
+#[derive(Accounts)]
+pub struct AmendOrderCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_AMEND_ORDER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(mut)]
+    pub order_account: Box<Account<'info, OrderAccount>>,
+    #[account(mut)]
+    pub book_page: Box<Account<'info, OrderBookPage>>,
+    #[account(mut)]
+    pub vault_state: Box<Account<'info, VaultState>>,
+    /// CHECK: the computation this callback answers, compared with the one holding the book
+    pub computation_account: UncheckedAccount<'info>,
+}
+
+#[event]
+pub struct OrderAmendedEvent {
+    pub order_id: u64,
+    pub user: Pubkey,
+    pub success: bool,
+    pub locked_amount: u64,  // the order's lock once the amendment has been applied or refused
+    pub orderbook_nonce: u128,
+    pub timestamp: i64,
+}
+
+#[callback_accounts("reveal_fills")]
+#[derive(Accounts)]
+pub struct RevealFillsCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_FILLS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub fill_receipt: Box<Account<'info, FillReceipt>>,
+}
+
+#[event]
+pub struct FillsRevealedEvent {
+    pub batch_id: u64,
+    pub order_id: u64,
+    pub user: Pubkey,
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; ORDER_FILLS_CIPHERTEXTS],
+}
+
+#[callback_accounts("reveal_tape")]
+#[derive(Accounts)]
+pub struct RevealTapeCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_TAPE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub match_batch: Box<Account<'info, MatchBatch>>,
+    #[account(mut)]
+    pub trade_tape: Box<Account<'info, TradeTape>>,
+}
+
+#[event]
+pub struct TradeTapeEvent {
+    pub batch_id: u64,
+    pub quantities: [u64; MAX_MATCHES_PER_BATCH],
+    pub prices: [u64; MAX_MATCHES_PER_BATCH],
+    pub count: u8,
+    pub timestamp: i64,
+}
+
+#[callback_accounts("reveal_settlement")]
+#[derive(Accounts)]
+pub struct RevealSettlementCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_SETTLEMENT))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub match_batch: Box<Account<'info, MatchBatch>>,
+}
+
+#[event]
+pub struct SettlementRevealedEvent {
+    pub batch_id: u64,
+    pub num_settlements: u8,
+}
+
+#[callback_accounts("verify_settlement")]
+#[derive(Accounts)]
+pub struct VerifySettlementCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_VERIFY_SETTLEMENT))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub match_record: Box<Account<'info, MatchRecord>>,
+}
+
+#[event]
+pub struct SettlementVerifiedEvent {
+    pub batch_id: u64,
+    pub match_id: u64,
+    pub verified: bool,
 }
 
 #[queue_computation_accounts("init_order_book", payer)]
@@ -325,10 +1063,50 @@ pub struct InitOrderBookCallback<'info> {
     pub orderbook_state: Box<Account<'info, OrderBookState>>,
 }
 
+#[callback_accounts("init_book_page")]
+#[derive(Accounts)]
+pub struct InitBookPageCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_INIT_BOOK_PAGE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub book_page: Box<Account<'info, OrderBookPage>>,
+}
+
+#[callback_accounts("rebalance_book")]
+#[derive(Accounts)]
+pub struct RebalanceBookCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REBALANCE_BOOK))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(mut)]
+    pub orderbook_state: Box<Account<'info, OrderBookState>>,
+    #[account(mut)]
+    pub book_page: Box<Account<'info, OrderBookPage>>,
+    /// CHECK: the computation this callback answers, compared with the one holding the book
+    pub computation_account: UncheckedAccount<'info>,
+}
+
+#[event]
+pub struct BookRebalancedEvent {
+    pub page_index: u8,
+    pub buy_count: u8,
+    pub sell_count: u8,
+    pub page_buy_count: u8,
+    pub page_sell_count: u8,
+}
+
 #[event]
 pub struct MatchResultEvent {
-    pub results: [u8; 32],
-    pub nonce: u128,
+    pub batch_id: u64,
+    pub match_ciphertexts: [[u8; 32]; MATCH_RESULT_CIPHERTEXTS],  // Enc<Shared, MatchResult>
+    pub match_nonce: u128,
     pub orderbook_nonce: u128,
     pub timestamp: i64,
 }
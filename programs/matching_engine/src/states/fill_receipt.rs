@@ -0,0 +1,19 @@
+use anchor_lang::prelude::*;
+
+use crate::states::MAX_MATCHES_PER_BATCH;
+
+// 3 ciphertexts per Fill plus num_fills
+pub const ORDER_FILLS_CIPHERTEXTS: usize = 3 * MAX_MATCHES_PER_BATCH + 1;
+
+#[account]
+#[derive(InitSpace)]
+pub struct FillReceipt {
+    pub batch_id: u64,
+    pub order_id: u64,
+    pub user: Pubkey,
+    pub enc_pubkey: [u8; 32],  // key the fills are encrypted to, copied from the order
+    pub fills_nonce: u128,
+    pub fills_data: [[u8; 32]; ORDER_FILLS_CIPHERTEXTS],  // Enc<Shared, OrderFills>
+    pub is_ready: bool,  // set once the reveal_fills callback has landed
+    pub bump: u8,
+}
@@ -0,0 +1,57 @@
+use anchor_lang::prelude::*;
+
+// Must match MAX_EVICTIONS_PER_BATCH in encrypted-ixs
+pub const MAX_EVICTIONS_PER_BATCH: usize = 29;
+
+// Must match MAX_MATCHES_PER_BATCH in encrypted-ixs
+pub const MAX_MATCHES_PER_BATCH: usize = 8;
+// 13 ciphertexts per MatchedOrder plus num_matches
+pub const MATCH_RESULT_CIPHERTEXTS: usize = 13 * MAX_MATCHES_PER_BATCH + 1;
+
+// One match revealed for on-chain settlement
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct PendingSettlement {
+    pub match_id: u64,
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub buyer_order_id: u64,
+    pub seller_order_id: u64,
+    pub quantity: u64,
+    pub execution_price: u64,
+    pub buyer_filled: bool,  // the match completes the buy order
+    pub seller_filled: bool,  // the match completes the sell order
+    pub buyer_owed: u64,  // what all of the buy order's fills draw from its lock, this one included
+    pub seller_owed: u64,
+    pub is_settled: bool,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct MatchBatch {
+    pub batch_id: u64,
+    pub match_data: [[u8; 32]; MATCH_RESULT_CIPHERTEXTS],  // Enc<Mxe, MatchResult>, read back by reveal_fills
+    pub match_nonce: u128,
+    pub result_data: [[u8; 32]; MATCH_RESULT_CIPHERTEXTS],  // Enc<Shared, MatchResult> for the backend key
+    pub result_nonce: u128,
+    pub evicted_order_ids: [u64; MAX_EVICTIONS_PER_BATCH],
+    pub evicted_owners: [Pubkey; MAX_EVICTIONS_PER_BATCH],
+    pub eviction_reasons: [u8; MAX_EVICTIONS_PER_BATCH],  // 1 = time in force, 2 = self-trade prevention, 3 = expiry
+    pub evicted_owed: [u64; MAX_EVICTIONS_PER_BATCH],  // collateral the evicted order's fills use, settled or not
+    pub eviction_finalized: [bool; MAX_EVICTIONS_PER_BATCH],
+    pub num_evictions: u8,
+    pub is_complete: bool,  // set once the match_orders callback has landed
+    pub tape_published: bool,  // set once the batch's tape has been written by the reveal_tape callback
+    pub settlement_ready: bool,  // set once the reveal_settlement callback has landed
+    pub settlements: [PendingSettlement; MAX_MATCHES_PER_BATCH],
+    pub num_settlements: u8,
+    pub timestamp: i64,
+    pub trade_tape: u8,  // market's tape setting when the batch was matched; 0 = never published
+    pub settlement_mode: u8,  // market's settlement mode when the batch was matched; the only path that can settle it
+    pub bump: u8,
+}
+
+impl MatchBatch {
+    // discriminator + batch_id
+    pub const MATCH_DATA_OFFSET: u32 = 8 + 8;
+    pub const MATCH_DATA_LEN: u32 = 32 * MATCH_RESULT_CIPHERTEXTS as u32;
+}
@@ -1,9 +1,53 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+
+// A match as the settlement bot claims it, opened from the batch's
+// Enc<Shared, MatchResult>
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct SettlementClaim {
+    pub match_id: u64,  // unique within its batch
+    pub buyer: Pubkey,
+    pub seller: Pubkey,
+    pub buyer_order_id: u64,
+    pub seller_order_id: u64,
+    pub quantity: u64,
+    pub execution_price: u64,
+    pub buyer_filled: bool,  // the match completes the buy order
+    pub seller_filled: bool,  // the match completes the sell order
+    pub buyer_owed: u64,  // what all of the buy order's fills draw from its lock, this one included
+    pub seller_owed: u64,
+}
+
+impl SettlementClaim {
+    // Commitment stored in the MatchRecord; execute_settlement must present a
+    // claim that hashes to it
+    pub fn hash(&self, batch_id: u64) -> [u8; 32] {
+        hashv(&[
+            &batch_id.to_le_bytes(),
+            &self.match_id.to_le_bytes(),
+            self.buyer.as_ref(),
+            self.seller.as_ref(),
+            &self.buyer_order_id.to_le_bytes(),
+            &self.seller_order_id.to_le_bytes(),
+            &self.quantity.to_le_bytes(),
+            &self.execution_price.to_le_bytes(),
+            &[self.buyer_filled as u8, self.seller_filled as u8],
+            &self.buyer_owed.to_le_bytes(),
+            &self.seller_owed.to_le_bytes(),
+        ])
+        .to_bytes()
+    }
+}
 
 #[account]
 #[derive(InitSpace)]
 pub struct MatchRecord {
-    pub match_id: u64,
+    pub batch_id: u64,  // MatchBatch the match was committed in
+    pub match_id: u64,  // unique within its batch
+    pub claim_hash: [u8; 32],  // SettlementClaim::hash of the committed claim
+    pub verification_pending: bool,  // verify_settlement is in flight; the claim cannot change until it lands
+    pub is_verified: bool,  // set once verify_settlement confirmed the batch holds the committed claim
     pub is_settled: bool,
     pub settlement_timestamp: i64,
-}
\ No newline at end of file
+    pub bump: u8,
+}
@@ -14,4 +14,16 @@ pub mod submit_order;
 pub use submit_order::*;
 
 pub mod match_record_struct;
-pub use match_record_struct::*;
\ No newline at end of file
+pub use match_record_struct::*;
+
+pub mod match_batch;
+pub use match_batch::*;
+
+pub mod fill_receipt;
+pub use fill_receipt::*;
+
+pub mod trade_tape;
+pub use trade_tape::*;
+
+pub mod order_book_page;
+pub use order_book_page::*;
@@ -5,10 +5,55 @@ use anchor_lang::prelude::*;
 pub struct OrderAccount {
     pub order_id: u64,
     pub user: Pubkey,
+    pub enc_pubkey: [u8; 32],  // x25519 key the order was encrypted with; fills are re-encrypted to it
     pub order_type: u8,  // 0 = buy, 1 = sell
-    pub status: u8,  // 0 = pending, 1 = submitted for processing, 2 = cancelled, 3 = partially filled, 4 = fully filled
-    pub locked_amount: u64,
+    pub order_kind: u8,  // 0 = limit, 1 = market
+    pub time_in_force: u8,  // 0 = GTC, 1 = IOC, 2 = FOK
+    pub post_only: u8,  // 0 = off, 1 = reject if crossing, 2 = reprice one tick away
+    pub is_stop: u8,  // 1 = stop / stop-limit, held in the trigger list until its encrypted trigger is hit
+    pub peg: u8,  // 0 = none, 1 = midpoint peg
+    pub page: u8,  // overflow page the order moves to while the top of the book is full
+    pub status: u8,  // 0 = pending, 1 = submitted for processing, 2 = cancelled, 3 = partially filled, 4 = fully filled, 5 = expired, 6 = rejected (post-only would cross), 7 = rejected (not covered by locked_amount)
+    pub locked_amount: u64,  // still reserved in the collateral vault (quote for buys, base for sells)
     pub filled_amount: u64,
+    pub spent_amount: u64,  // drawn from the lock by settled fills
     pub timestamp: i64,
+    pub last_amended: i64,
+    pub amend_locked_total: u64,  // locked + spent the in-flight amendment was checked against
+    pub amend_reserved: u64,  // locked on top for the in-flight amendment, given back if it is not applied
+    pub expiry: i64,  // 0 = good till cancelled
     pub bump: u8,
+}
+
+impl OrderAccount {
+    // Once the order has left the book, only what its unsettled fills will
+    // still draw stays locked. `owed` is what all of its fills use, as the
+    // MPC counted it; returns the amount that goes back to the vault.
+    pub fn release_unowed(&mut self, owed: u64) -> u64 {
+        let outstanding = owed
+            .saturating_sub(self.spent_amount)
+            .min(self.locked_amount);
+        let released = self.locked_amount - outstanding;
+        self.locked_amount = outstanding;
+        // A reserve for an amendment in flight is part of what went back
+        self.amend_reserved = 0;
+        released
+    }
+
+    // Settles the lock once an amendment's callback lands. An applied
+    // amendment leaves the order locked for what it asked for, less what
+    // fills settled since then drew; otherwise what was reserved for it goes
+    // back. Returns the amount that goes back to the vault.
+    pub fn finish_amendment(&mut self, applied: bool) -> u64 {
+        let released = if applied {
+            let target = self.amend_locked_total.saturating_sub(self.spent_amount);
+            self.locked_amount.saturating_sub(target)
+        } else {
+            self.amend_reserved.min(self.locked_amount)
+        };
+        self.locked_amount -= released;
+        self.amend_locked_total = 0;
+        self.amend_reserved = 0;
+        released
+    }
 }
\ No newline at end of file
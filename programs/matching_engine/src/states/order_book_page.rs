@@ -0,0 +1,52 @@
+use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
+use crate::states::{ORDER_CIPHERTEXTS, PENDING_COMPUTATION_TIMEOUT};
+
+// Mirrors BookPage in encrypted-ixs. With every page initialized a side
+// holds MAX_ORDERS at the top of the book plus MAX_BOOK_PAGES * PAGE_ORDERS
+// paged orders, 261 in all.
+pub const PAGE_ORDERS: usize = 8;
+pub const MAX_BOOK_PAGES: u8 = 32;
+// buy and sell lists with their counts
+pub const BOOK_PAGE_CIPHERTEXTS: usize = 2 * PAGE_ORDERS * ORDER_CIPHERTEXTS + 2;
+
+#[account]
+#[derive(InitSpace)]
+pub struct OrderBookPage {
+    pub page_data: [[u8; 32]; BOOK_PAGE_CIPHERTEXTS], // 9792
+    pub page_nonce: u128,     // 16
+    pub page_index: u8,       // 1
+    pub buy_count: u8,        // 1, revealed so clients can pick a page with room
+    pub sell_count: u8,       // 1
+    pub is_ready: bool,       // 1, set once the init_book_page callback has written the empty page
+    pub bump: u8,             // 1
+    pub pending_computation: Pubkey, // 32, in-flight computation that will rewrite the page, default when idle
+    pub pending_since: i64,   // 8
+}
+// Total: 9853 bytes
+
+impl OrderBookPage {
+    // The encrypted page starts right after the discriminator
+    pub const PAGE_OFFSET: u32 = 8;
+    pub const PAGE_LEN: u32 = (BOOK_PAGE_CIPHERTEXTS * 32) as u32;
+
+    // Same guard as OrderBookState::begin_computation, for the page
+    pub fn begin_computation(&mut self, computation: Pubkey, now: i64) -> Result<()> {
+        require!(
+            self.pending_computation == Pubkey::default()
+                || now >= self.pending_since + PENDING_COMPUTATION_TIMEOUT,
+            ErrorCode::OrderBookBusy
+        );
+        self.pending_computation = computation;
+        self.pending_since = now;
+        Ok(())
+    }
+
+    pub fn end_computation(&mut self, computation: Pubkey) -> bool {
+        let holds = self.pending_computation == computation;
+        if holds {
+            self.pending_computation = Pubkey::default();
+        }
+        holds
+    }
+}
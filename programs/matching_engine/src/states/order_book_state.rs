@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use crate::errors::ErrorCode;
 
 // #[account]
 // #[derive(InitSpace)]
@@ -40,11 +41,27 @@ use anchor_lang::prelude::*;
 // }
 
 
+// Mirrors the OrderBook layout in encrypted-ixs, where every scalar is one
+// 32-byte ciphertext. Keep these in sync with the circuit constants.
+pub const MAX_ORDERS: usize = 5;
+pub const MAX_STOP_ORDERS: usize = 3;
+pub const ORDER_CIPHERTEXTS: usize = 19;
+// buy and sell heaps with their counts, stop list (order + trigger) with its
+// count, last execution price
+pub const ORDERBOOK_CIPHERTEXTS: usize = 2 * MAX_ORDERS * ORDER_CIPHERTEXTS
+    + 2
+    + MAX_STOP_ORDERS * (ORDER_CIPHERTEXTS + 1)
+    + 2;
+
+// Seconds a queued computation may hold the book (or a page) before another
+// one may take it over. The late callback is then ignored.
+pub const PENDING_COMPUTATION_TIMEOUT: i64 = 120;
+
 #[account]
 #[derive(InitSpace)]
 pub struct OrderBookState {
     pub authority: Pubkey,              // 32
-    pub orderbook_data: [[u8; 32]; 52], // 1312
+    pub orderbook_data: [[u8; 32]; ORDERBOOK_CIPHERTEXTS], // 8128
     pub orderbook_nonce: u128,          // 16
     pub backend_pubkey: [u8; 32],       // 32
     pub base_mint: Pubkey,              // 32
@@ -53,5 +70,83 @@ pub struct OrderBookState {
     pub total_orders_processed: u64,    // 8
     pub total_matches: u64,             // 8
     pub bump: u8,                       // 1
+    pub batch_count: u64,               // 8
+    pub config: MarketConfig,           // 30
+    pub settlement_authority: Pubkey,   // 32, signs commit_settlement and execute_settlement
+    pub pending_computation: Pubkey,    // 32, computation account of the in-flight computation that will rewrite the book, default when idle
+    pub pending_since: i64,             // 8
+}
+// Total: 8407 bytes
+
+impl OrderBookState {
+    // Byte range of the encrypted book inside the account, as read by
+    // `Argument::Account`: it starts right after the discriminator and the
+    // authority.
+    pub const ORDERBOOK_OFFSET: u32 = 8 + 32;
+    pub const ORDERBOOK_LEN: u32 = (ORDERBOOK_CIPHERTEXTS * 32) as u32;
+
+    // Claims the book for a computation about to be queued. Every circuit
+    // reads the whole book and its callback writes the whole book back, so a
+    // second one queued before the first lands would undo it. Refused while
+    // another computation holds the book, unless that one timed out.
+    pub fn begin_computation(&mut self, computation: Pubkey, now: i64) -> Result<()> {
+        require!(
+            self.pending_computation == Pubkey::default()
+                || now >= self.pending_since + PENDING_COMPUTATION_TIMEOUT,
+            ErrorCode::OrderBookBusy
+        );
+        self.pending_computation = computation;
+        self.pending_since = now;
+        Ok(())
+    }
+
+    // Frees the book if `computation` holds it. A callback whose computation
+    // no longer holds it computed on a stale book and must not write it.
+    pub fn end_computation(&mut self, computation: Pubkey) -> bool {
+        let holds = self.pending_computation == computation;
+        if holds {
+            self.pending_computation = Pubkey::default();
+        }
+        holds
+    }
+
+    // Mint an order's funds are locked in: quote for a buy, base for a sell
+    pub fn collateral_mint(&self, order_type: u8) -> Pubkey {
+        if order_type == 0 {
+            self.quote_mint
+        } else {
+            self.base_mint
+        }
+    }
+}
+
+/// Per-market trading parameters, set at initialization and adjustable by
+/// the orderbook authority.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct MarketConfig {
+    pub tick_size: u64, // 8, price increment used when repricing post-only orders
+    pub self_trade_policy: u8, // 1, 0 = cancel resting, 1 = cancel incoming, 2 = cancel both
+    pub price_rule: u8, // 1, 0 = midpoint, 1 = resting price, 2 = aggressor price, 3 = midpoint rounded to tick
+    pub matching_mode: u8, // 1, 0 = continuous, 1 = uniform-price batch auction
+    pub allocation: u8, // 1, 0 = price-time, 1 = pro-rata within a price level
+    pub min_allocation: u64, // 8, smallest pro-rata share before the remainder is handed out
+    pub trade_tape: u8, // 1, 0 = off, 1 = every trade, 2 = one aggregated entry per batch
+    pub tape_delay: i64, // 8, seconds after a batch before its tape can be published
+    pub settlement_mode: u8, // 1, 0 = settlement bot via execute_settlement, 1 = on-chain from the revealed batch
+}
+
+impl MarketConfig {
+    pub fn validate(&self) -> Result<()> {
+        require!(self.tick_size > 0, ErrorCode::InvalidTickSize);
+        require!(self.self_trade_policy <= 2, ErrorCode::InvalidSelfTradePolicy);
+        require!(self.price_rule <= 3, ErrorCode::InvalidPriceRule);
+        require!(self.matching_mode <= 1, ErrorCode::InvalidMatchingMode);
+        require!(self.allocation <= 1, ErrorCode::InvalidAllocation);
+        require!(
+            self.trade_tape <= 2 && self.tape_delay >= 0,
+            ErrorCode::InvalidTradeTape
+        );
+        require!(self.settlement_mode <= 1, ErrorCode::InvalidSettlementMode);
+        Ok(())
+    }
 }
-// Total: 1481 bytes
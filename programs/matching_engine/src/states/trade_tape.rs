@@ -0,0 +1,30 @@
+use anchor_lang::prelude::*;
+
+pub const TAPE_CAPACITY: usize = 64;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct TapeEntry {
+    pub batch_id: u64,
+    pub quantity: u64,
+    pub price: u64,  // execution price, or the volume-weighted price of an aggregated batch
+    pub timestamp: i64,  // when the batch was matched
+}
+
+// Ring buffer of published trades; the oldest entry is overwritten once full.
+// Indexers read `total_entries` to find new entries since their last poll.
+#[account]
+#[derive(InitSpace)]
+pub struct TradeTape {
+    pub entries: [TapeEntry; TAPE_CAPACITY],
+    pub head: u32,  // next slot to write
+    pub total_entries: u64,
+    pub bump: u8,
+}
+
+impl TradeTape {
+    pub fn push(&mut self, entry: TapeEntry) {
+        self.entries[self.head as usize] = entry;
+        self.head = (self.head + 1) % TAPE_CAPACITY as u32;
+        self.total_entries = self.total_entries.saturating_add(1);
+    }
+}
@@ -8,6 +8,11 @@ const ORDERBOOK_SEED = Buffer.from("order_book_state");
 const VAULT_SEED = Buffer.from("vault");
 const VAULT_STATE_SEED = Buffer.from("vault_state");
 const ORDER_SEED = Buffer.from("order");
+const BOOK_PAGE_SEED = Buffer.from("order_book_page");
+const MATCH_BATCH_SEED = Buffer.from("match_batch");
+const MATCH_RECORD_SEED = Buffer.from("match_record");
+const FILL_RECEIPT_SEED = Buffer.from("fill_receipt");
+const TRADE_TAPE_SEED = Buffer.from("trade_tape");
 
 /**
  * Derive OrderBookState PDA
@@ -18,6 +23,79 @@ export function deriveOrderbookPDA(
   return PublicKey.findProgramAddressSync([ORDERBOOK_SEED], programId);
 }
 
+/**
+ * Derive OrderBookPage PDA
+ */
+export function deriveBookPagePDA(
+  pageIndex: number,
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [BOOK_PAGE_SEED, Buffer.from([pageIndex])],
+    programId
+  );
+}
+
+/**
+ * Derive MatchBatch PDA
+ */
+export function deriveMatchBatchPDA(
+  batchId: anchor.BN,
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [MATCH_BATCH_SEED, batchId.toArrayLike(Buffer, "le", 8)],
+    programId
+  );
+}
+
+/**
+ * Derive MatchRecord PDA
+ */
+export function deriveMatchRecordPDA(
+  batchId: anchor.BN,
+  matchId: anchor.BN,
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [
+      MATCH_RECORD_SEED,
+      batchId.toArrayLike(Buffer, "le", 8),
+      matchId.toArrayLike(Buffer, "le", 8),
+    ],
+    programId
+  );
+}
+
+/**
+ * Derive FillReceipt PDA
+ */
+export function deriveFillReceiptPDA(
+  batchId: anchor.BN,
+  orderId: anchor.BN,
+  userPubkey: PublicKey,
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [
+      FILL_RECEIPT_SEED,
+      batchId.toArrayLike(Buffer, "le", 8),
+      orderId.toArrayLike(Buffer, "le", 8),
+      userPubkey.toBuffer(),
+    ],
+    programId
+  );
+}
+
+/**
+ * Derive TradeTape PDA
+ */
+export function deriveTradeTapePDA(
+  programId: PublicKey
+): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync([TRADE_TAPE_SEED], programId);
+}
+
 /**
  * Derive OrderAccount PDA
  */
@@ -209,4 +209,90 @@ export async function initInitOrderBookCompDef(
     finalizeTx.lastValidBlockHeight = latestBlockhash.lastValidBlockHeight;
     return sig;
   }
-}
\ No newline at end of file
+}
+
+/**
+ * Initialize init_book_page computation definition
+ */
+export async function initInitBookPageCompDef(
+  program: Program<MatchingEngine>,
+  owner: Keypair,
+  uploadRawCircuit: boolean = false,
+  offchainSource: boolean = false
+): Promise<string> {
+  return initCompDef(
+    program,
+    owner,
+    "init_book_page",
+    "initBookPageCompDef",
+    uploadRawCircuit,
+    offchainSource
+  );
+}
+
+/**
+ * Initialize the computation definition of any circuit through the program's
+ * init_*_comp_def instruction
+ */
+export async function initCompDef(
+  program: Program<MatchingEngine>,
+  owner: Keypair,
+  circuitName: string,
+  initMethod: string,
+  uploadRawCircuit: boolean = false,
+  offchainSource: boolean = false
+): Promise<string> {
+  const baseSeedCompDefAcc = getArciumAccountBaseSeed(
+    "ComputationDefinitionAccount"
+  );
+  const offset = getCompDefAccOffset(circuitName);
+
+  const compDefPDA = PublicKey.findProgramAddressSync(
+    [baseSeedCompDefAcc, program.programId.toBuffer(), offset],
+    getArciumProgAddress()
+  )[0];
+
+  console.log(`${circuitName} comp def PDA:`, compDefPDA.toBase58());
+
+  const sig = await program.methods[initMethod]()
+    .accounts({
+      compDefAccount: compDefPDA,
+      payer: owner.publicKey,
+      mxeAccount: getMXEAccAddress(program.programId),
+    })
+    .signers([owner])
+    .rpc({
+      commitment: "confirmed",
+    });
+
+  console.log(`Init ${circuitName} computation definition tx:`, sig);
+
+  const provider = program.provider as anchor.AnchorProvider;
+
+  if (uploadRawCircuit) {
+    const rawCircuit = fs.readFileSync(`build/${circuitName}.arcis`);
+    await uploadCircuit(
+      provider,
+      circuitName,
+      program.programId,
+      rawCircuit,
+      true
+    );
+  } else if (!offchainSource) {
+    const finalizeTx = await buildFinalizeCompDefTx(
+      provider,
+      Buffer.from(offset).readUInt32LE(),
+      program.programId
+    );
+
+    const latestBlockhash = await provider.connection.getLatestBlockhash();
+    finalizeTx.recentBlockhash = latestBlockhash.blockhash;
+    finalizeTx.lastValidBlockHeight = latestBlockhash.lastValidBlockHeight;
+
+    finalizeTx.sign(owner);
+
+    await provider.sendAndConfirm(finalizeTx);
+  }
+
+  return sig;
+}
@@ -34,12 +34,19 @@ import * as os from "os";
 import { expect } from "chai";
 import {
   setupUserEncryption,
+  setupBackendEncryption,
   getMXEPublicKeyWithRetry,
   generateNonce,
+  EncryptionSetup,
 } from "./helpers/encryption";
 import {
   deriveOrderbookPDA,
   deriveOrderAccountPDA,
+  deriveBookPagePDA,
+  deriveMatchBatchPDA,
+  deriveMatchRecordPDA,
+  deriveFillReceiptPDA,
+  deriveTradeTapePDA,
   deriveVaultStatePDA,
   deriveVaultAuthorityPDA,
   getOrderBookState,
@@ -55,6 +62,8 @@ import {
   initSubmitOrderCompDef,
   initMatchOrdersCompDef,
   initInitOrderBookCompDef,
+  initInitBookPageCompDef,
+  initCompDef,
   readKpJson,
 } from "./helpers/computation";
 
@@ -77,6 +86,8 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
   let user1token2ATA: PublicKey;
   let user2token1ATA: PublicKey;
   let user2token2ATA: PublicKey;
+  // x25519 key the backend decrypts match results with, set in Test 1.1.1
+  let backendSecretKey: Uint8Array;
 
   // Event helper
   type Event = anchor.IdlEvents<typeof program.idl>;
@@ -100,6 +111,576 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
     return event;
   };
 
+  // Shared by the behavioural suites from 1.7 on; encryption is set up in
+  // Suite 1.7
+  let user1Encryption: EncryptionSetup;
+  let user2Encryption: EncryptionSetup;
+
+  const sleep = (ms: number) =>
+    new Promise((resolve) => setTimeout(resolve, ms));
+
+  // Accounts every queued computation takes
+  const arciumAccounts = (circuit: string, computationOffset: anchor.BN) => ({
+    computationAccount: getComputationAccAddress(
+      program.programId,
+      computationOffset
+    ),
+    signPdaAccount: deriveSignerAccountPDA(program.programId),
+    poolAccount: deriveArciumFeePoolAccountAddress(),
+    clusterAccount: arciumEnv.arciumClusterPubkey,
+    mxeAccount: getMXEAccAddress(program.programId),
+    mempoolAccount: getMempoolAccAddress(program.programId),
+    executingPool: getExecutingPoolAccAddress(program.programId),
+    compDefAccount: getCompDefAccAddress(
+      program.programId,
+      Buffer.from(getCompDefAccOffset(circuit)).readUInt32LE()
+    ),
+    clockAccount: getClockAccAddress(),
+    systemProgram: SystemProgram.programId,
+    arciumProgram: getArciumProgramId(),
+  });
+
+  const awaitFinalization = (computationOffset: anchor.BN) =>
+    awaitComputationFinalization(
+      provider,
+      computationOffset,
+      program.programId,
+      "confirmed"
+    );
+
+  const orderPDA = (orderId: number, user: Keypair) =>
+    deriveOrderAccountPDA(new BN(orderId), user.publicKey, program.programId)[0];
+
+  const fetchOrder = (orderId: number, user: Keypair) =>
+    program.account.orderAccount.fetch(orderPDA(orderId, user));
+
+  // A buy is collateralized by the quote vault, a sell by the base vault
+  const collateralMint = (side: number) => (side === 0 ? quoteMint : baseMint);
+
+  const fetchLocked = async (mint: PublicKey, user: Keypair) => {
+    const [vaultStatePDA] = deriveVaultStatePDA(
+      mint,
+      user.publicKey,
+      program.programId
+    );
+    const vaultState = await program.account.vaultState.fetch(vaultStatePDA);
+    return vaultState.lockedAmount.toNumber();
+  };
+
+  const expectError = async (tx: Promise<unknown>, code: string) => {
+    try {
+      await tx;
+    } catch (error) {
+      expect(error.error?.errorCode?.code).to.equal(code);
+      return;
+    }
+    expect.fail(`Expected ${code}`);
+  };
+
+  const initializeVault = async (mint: PublicKey, user: Keypair) => {
+    const [vaultPDA] = deriveVaultPDA(mint, user.publicKey, program.programId);
+    if (await accountExists(provider, vaultPDA)) return;
+    await program.methods
+      .initializeVault()
+      .accountsPartial({
+        user: user.publicKey,
+        mint,
+        vault: vaultPDA,
+        vaultState: deriveVaultStatePDA(mint, user.publicKey, program.programId)[0],
+        vaultAuthority: deriveVaultAuthorityPDA(program.programId)[0],
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+  };
+
+  const deposit = (
+    mint: PublicKey,
+    user: Keypair,
+    userTokenAccount: PublicKey,
+    amount: number
+  ) =>
+    program.methods
+      .depositToVault(new BN(amount))
+      .accountsPartial({
+        user: user.publicKey,
+        userTokenAccount,
+        vault: deriveVaultPDA(mint, user.publicKey, program.programId)[0],
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+
+  const updateConfig = async (changes: object) => {
+    const { config } = await getOrderBookState(program);
+    await program.methods
+      .updateMarketConfig({ ...config, ...changes })
+      .accountsPartial({
+        authority: authority.publicKey,
+        orderbookState: OrderbookPDA,
+      })
+      .signers([authority])
+      .rpc({ commitment: "confirmed" });
+  };
+
+  const setSettlementMode = (settlementMode: number) =>
+    updateConfig({ settlementMode });
+
+  // What an order is besides its side, amount and price; left out, it is a
+  // plain good-till-cancelled limit order
+  type OrderTerms = {
+    market?: boolean; // the price is then the protection limit
+    timeInForce?: number; // 0 = GTC, 1 = IOC, 2 = FOK
+    postOnly?: number; // 1 = reject if crossing, 2 = reprice one tick away
+    displayAmount?: number; // iceberg slice, the rest stays hidden
+    stopTrigger?: number; // held back until the last price reaches it
+    pegged?: boolean; // follows the midpoint; the price is then its hidden limit
+    minFill?: number; // smallest fill the order accepts
+    expiry?: number; // unix time the order stops being good
+  };
+
+  // Order locking exactly what it can trade: quantity * limit of quote for
+  // a buy, quantity of base for a sell. Resolves once the order is queued
+  // and returns its computation offset.
+  const queueSubmit = async (
+    user: Keypair,
+    encryption: EncryptionSetup,
+    orderId: number,
+    side: number,
+    amount: number,
+    price: number,
+    terms: OrderTerms = {}
+  ) => {
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    const nonce = randomBytes(16);
+    const ciphertext = encryption.cipher.encrypt(
+      [
+        BigInt(amount),
+        BigInt(price),
+        BigInt(terms.displayAmount ?? 0),
+        BigInt(terms.stopTrigger ?? 0),
+        BigInt(terms.minFill ?? 0),
+      ],
+      nonce
+    );
+    const mint = collateralMint(side);
+
+    await program.methods
+      .submitOrder(
+        Array.from(ciphertext[0]),
+        Array.from(ciphertext[1]),
+        Array.from(ciphertext[2]),
+        Array.from(ciphertext[3]),
+        Array.from(ciphertext[4]),
+        Array.from(encryption.publicKey),
+        side,
+        terms.market ? 1 : 0,
+        terms.timeInForce ?? 0,
+        terms.postOnly ?? 0,
+        terms.stopTrigger === undefined ? 0 : 1,
+        terms.pegged ? 1 : 0,
+        new anchor.BN(terms.expiry ?? 0),
+        new anchor.BN(side === 0 ? amount * price : amount),
+        computationOffset,
+        new anchor.BN(orderId),
+        new anchor.BN(deserializeLE(nonce).toString())
+      )
+      .accountsPartial({
+        ...arciumAccounts("submit_order", computationOffset),
+        user: user.publicKey,
+        mint,
+        vault: deriveVaultPDA(mint, user.publicKey, program.programId)[0],
+        orderAccount: orderPDA(orderId, user),
+        vaultState: deriveVaultStatePDA(mint, user.publicKey, program.programId)[0],
+        orderbookState: OrderbookPDA,
+        bookPage: deriveBookPagePDA(0, program.programId)[0],
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+
+    return computationOffset;
+  };
+
+  const submit = async (
+    user: Keypair,
+    encryption: EncryptionSetup,
+    orderId: number,
+    side: number,
+    amount: number,
+    price: number,
+    terms: OrderTerms = {}
+  ) => {
+    await awaitFinalization(
+      await queueSubmit(user, encryption, orderId, side, amount, price, terms)
+    );
+    const order = await fetchOrder(orderId, user);
+    expect(order.status).to.equal(1, "order should rest in the book");
+  };
+
+  const cancel = async (user: Keypair, orderId: number) => {
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    const order = await fetchOrder(orderId, user);
+    const mint = collateralMint(order.orderType);
+
+    await program.methods
+      .cancelOrder(computationOffset, new anchor.BN(orderId))
+      .accountsPartial({
+        ...arciumAccounts("cancel_order", computationOffset),
+        user: user.publicKey,
+        orderAccount: orderPDA(orderId, user),
+        vaultState: deriveVaultStatePDA(mint, user.publicKey, program.programId)[0],
+        orderbookState: OrderbookPDA,
+        bookPage: deriveBookPagePDA(order.page, program.programId)[0],
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+
+    await awaitFinalization(computationOffset);
+  };
+
+  // Replaces a resting order's amount and price; `lockedAmount` is what the
+  // order should hold locked once amended
+  const amend = async (
+    user: Keypair,
+    encryption: EncryptionSetup,
+    orderId: number,
+    amount: number,
+    price: number,
+    lockedAmount: number,
+    displayAmount: number = 0
+  ) => {
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    const nonce = randomBytes(16);
+    const ciphertext = encryption.cipher.encrypt(
+      [BigInt(amount), BigInt(price), BigInt(displayAmount)],
+      nonce
+    );
+    const order = await fetchOrder(orderId, user);
+    const mint = collateralMint(order.orderType);
+
+    await program.methods
+      .amendOrder(
+        Array.from(ciphertext[0]),
+        Array.from(ciphertext[1]),
+        Array.from(ciphertext[2]),
+        Array.from(encryption.publicKey),
+        new anchor.BN(lockedAmount),
+        computationOffset,
+        new anchor.BN(orderId),
+        new anchor.BN(deserializeLE(nonce).toString())
+      )
+      .accountsPartial({
+        ...arciumAccounts("amend_order", computationOffset),
+        user: user.publicKey,
+        orderAccount: orderPDA(orderId, user),
+        vault: deriveVaultPDA(mint, user.publicKey, program.programId)[0],
+        vaultState: deriveVaultStatePDA(mint, user.publicKey, program.programId)[0],
+        orderbookState: OrderbookPDA,
+        bookPage: deriveBookPagePDA(order.page, program.programId)[0],
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+
+    await awaitFinalization(computationOffset);
+  };
+
+  // Runs match_orders once the 15s rate limit allows it and returns the
+  // id of the batch it wrote
+  const triggerMatching = async (): Promise<anchor.BN> => {
+    const state = await getOrderBookState(program);
+    const wait =
+      state.lastMatchTimestamp.toNumber() + 16 - Math.floor(Date.now() / 1000);
+    if (wait > 0) await sleep(wait * 1000);
+
+    const batchId = state.batchCount;
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    await program.methods
+      .triggerMatching(computationOffset, 0)
+      .accountsPartial({
+        ...arciumAccounts("match_orders", computationOffset),
+        payer: authority.publicKey,
+        orderbookState: OrderbookPDA,
+        matchBatch: deriveMatchBatchPDA(batchId, program.programId)[0],
+        bookPage: deriveBookPagePDA(0, program.programId)[0],
+      })
+      .signers([authority])
+      .rpc({ commitment: "confirmed" });
+
+    await awaitFinalization(computationOffset);
+    const batch = await fetchBatch(batchId);
+    expect(batch.isComplete).to.be.true;
+    return batchId;
+  };
+
+  const fetchBatch = (batchId: anchor.BN) =>
+    program.account.matchBatch.fetch(
+      deriveMatchBatchPDA(batchId, program.programId)[0]
+    );
+
+  // Finalizes an order the batch evicted and returns the eviction's reason
+  // and what the order's fills still owe
+  const finalizeEviction = async (
+    batchId: anchor.BN,
+    orderId: number,
+    user: Keypair
+  ) => {
+    const batch = await fetchBatch(batchId);
+    const index = batch.evictedOrderIds.findIndex(
+      (id, i) =>
+        i < batch.numEvictions &&
+        id.toNumber() === orderId &&
+        batch.evictedOwners[i].equals(user.publicKey)
+    );
+    expect(index).to.be.at.least(0, `order ${orderId} should be evicted`);
+    const order = await fetchOrder(orderId, user);
+    const mint = collateralMint(order.orderType);
+
+    await program.methods
+      .finalizeEvictedOrder(batchId, index)
+      .accountsPartial({
+        payer: authority.publicKey,
+        matchBatch: deriveMatchBatchPDA(batchId, program.programId)[0],
+        orderAccount: orderPDA(orderId, user),
+        orderbookState: OrderbookPDA,
+        vaultState: deriveVaultStatePDA(mint, user.publicKey, program.programId)[0],
+      })
+      .signers([authority])
+      .rpc({ commitment: "confirmed" });
+
+    return {
+      reason: batch.evictionReasons[index],
+      owed: batch.evictedOwed[index].toNumber(),
+    };
+  };
+
+  // Reads a batch the way the backend does: decrypts the Enc<Shared,
+  // MatchResult> kept in its MatchBatch with the backend key. Each match is
+  // 13 fields (match id, buyer and seller as two halves each, order ids,
+  // quantity, price, both filled flags, both owed amounts) and the match
+  // count follows the last slot.
+  const MATCH_FIELDS = 13;
+  const MAX_MATCHES_PER_BATCH = 8;
+  const decryptBatch = async (batchId: anchor.BN) => {
+    const batch = await fetchBatch(batchId);
+    return decryptMatches(batch.resultNonce, batch.resultData);
+  };
+
+  // Same decryption for ciphertexts taken from a MatchResultEvent
+  const decryptMatches = async (nonce: anchor.BN, ciphertexts: number[][]) => {
+    const backend = await setupBackendEncryption(
+      provider,
+      program.programId,
+      backendSecretKey
+    );
+    const fields = backend.cipher.decrypt(
+      ciphertexts,
+      Uint8Array.from(nonce.toArray("le", 16))
+    );
+    const numMatches = Number(fields[MATCH_FIELDS * MAX_MATCHES_PER_BATCH]);
+    return Array.from({ length: numMatches }, (_, i) => {
+      const f = fields.slice(i * MATCH_FIELDS, (i + 1) * MATCH_FIELDS);
+      return {
+        matchId: Number(f[0]),
+        buyerOrderId: Number(f[5]),
+        sellerOrderId: Number(f[6]),
+        quantity: Number(f[7]),
+        executionPrice: Number(f[8]),
+        buyerFilled: f[9] === BigInt(1),
+        sellerFilled: f[10] === BigInt(1),
+        buyerOwed: Number(f[11]),
+        sellerOwed: Number(f[12]),
+      };
+    });
+  };
+
+  // Queues reveal_fills for an order signed by `user`, who must own it;
+  // `owner` names whose order PDA is passed
+  const revealFills = async (
+    user: Keypair,
+    batchId: anchor.BN,
+    orderId: number,
+    owner: Keypair = user
+  ) => {
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    const nonce = randomBytes(16);
+    await program.methods
+      .revealFills(
+        computationOffset,
+        batchId,
+        new anchor.BN(orderId),
+        new anchor.BN(deserializeLE(nonce).toString())
+      )
+      .accountsPartial({
+        ...arciumAccounts("reveal_fills", computationOffset),
+        user: user.publicKey,
+        matchBatch: deriveMatchBatchPDA(batchId, program.programId)[0],
+        orderAccount: orderPDA(orderId, owner),
+        fillReceipt: deriveFillReceiptPDA(
+          batchId,
+          new anchor.BN(orderId),
+          user.publicKey,
+          program.programId
+        )[0],
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+
+    await awaitFinalization(computationOffset);
+  };
+
+  // Reads a fill receipt with the key its order was submitted with: 3
+  // fields per fill (match id, quantity, price) and the count last
+  const decryptFills = (
+    encryption: EncryptionSetup,
+    nonce: anchor.BN,
+    ciphertexts: number[][]
+  ) => {
+    const fields = encryption.cipher.decrypt(
+      ciphertexts,
+      Uint8Array.from(nonce.toArray("le", 16))
+    );
+    const numFills = Number(fields[3 * MAX_MATCHES_PER_BATCH]);
+    return Array.from({ length: numFills }, (_, i) => ({
+      matchId: Number(fields[3 * i]),
+      quantity: Number(fields[3 * i + 1]),
+      executionPrice: Number(fields[3 * i + 2]),
+    }));
+  };
+
+  // Publishes a batch's trades to the tape; anyone can crank it
+  const publishTape = async (batchId: anchor.BN) => {
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    await program.methods
+      .publishTradeTape(computationOffset, batchId)
+      .accountsPartial({
+        ...arciumAccounts("reveal_tape", computationOffset),
+        payer: authority.publicKey,
+        orderbookState: OrderbookPDA,
+        matchBatch: deriveMatchBatchPDA(batchId, program.programId)[0],
+        tradeTape: deriveTradeTapePDA(program.programId)[0],
+      })
+      .signers([authority])
+      .rpc({ commitment: "confirmed" });
+
+    await awaitFinalization(computationOffset);
+  };
+
+  const fetchTape = () =>
+    program.account.tradeTape.fetch(deriveTradeTapePDA(program.programId)[0]);
+
+  const revealSettlement = async (batchId: anchor.BN) => {
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    await program.methods
+      .revealSettlement(computationOffset, batchId)
+      .accountsPartial({
+        ...arciumAccounts("reveal_settlement", computationOffset),
+        payer: authority.publicKey,
+        orderbookState: OrderbookPDA,
+        matchBatch: deriveMatchBatchPDA(batchId, program.programId)[0],
+      })
+      .signers([authority])
+      .rpc({ commitment: "confirmed" });
+
+    await awaitFinalization(computationOffset);
+    const batch = await fetchBatch(batchId);
+    expect(batch.settlementReady).to.be.true;
+    return batch;
+  };
+
+  const settlementAccounts = (
+    buyer: Keypair,
+    buyerOrderId: number,
+    seller: Keypair,
+    sellerOrderId: number
+  ) => ({
+    vaultAuthority: deriveVaultAuthorityPDA(program.programId)[0],
+    orderbookState: OrderbookPDA,
+    buyerOrder: orderPDA(buyerOrderId, buyer),
+    sellerOrder: orderPDA(sellerOrderId, seller),
+    buyerBaseVault: deriveVaultPDA(baseMint, buyer.publicKey, program.programId)[0],
+    buyerQuoteVault: deriveVaultPDA(quoteMint, buyer.publicKey, program.programId)[0],
+    buyerQuoteVaultState: deriveVaultStatePDA(quoteMint, buyer.publicKey, program.programId)[0],
+    sellerBaseVault: deriveVaultPDA(baseMint, seller.publicKey, program.programId)[0],
+    sellerQuoteVault: deriveVaultPDA(quoteMint, seller.publicKey, program.programId)[0],
+    sellerBaseVaultState: deriveVaultStatePDA(baseMint, seller.publicKey, program.programId)[0],
+    tokenProgram: TOKEN_PROGRAM_ID,
+  });
+
+  const settleMatch = (
+    batchId: anchor.BN,
+    index: number,
+    buyerOrderId: number,
+    sellerOrderId: number
+  ) =>
+    program.methods
+      .settleMatch(batchId, index)
+      .accountsPartial({
+        payer: authority.publicKey,
+        matchBatch: deriveMatchBatchPDA(batchId, program.programId)[0],
+        settlement: settlementAccounts(user1, buyerOrderId, user2, sellerOrderId),
+      })
+      .signers([authority])
+      .rpc({ commitment: "confirmed" });
+
+  // Queues verify_settlement for a claim; resolves once the commit landed,
+  // not once the MPC answered
+  const commitSettlement = async (
+    batchId: anchor.BN,
+    claim: any,
+    signer: Keypair = authority
+  ) => {
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
+    await program.methods
+      .commitSettlement(computationOffset, batchId, claim)
+      .accountsPartial({
+        ...arciumAccounts("verify_settlement", computationOffset),
+        settlementAuthority: signer.publicKey,
+        orderbookState: OrderbookPDA,
+        matchBatch: deriveMatchBatchPDA(batchId, program.programId)[0],
+        buyerOrder: orderPDA(claim.buyerOrderId.toNumber(), user1),
+        sellerOrder: orderPDA(claim.sellerOrderId.toNumber(), user2),
+        matchRecord: deriveMatchRecordPDA(batchId, claim.matchId, program.programId)[0],
+      })
+      .signers([signer])
+      .rpc({ commitment: "confirmed" });
+    return computationOffset;
+  };
+
+  const executeSettlement = (
+    batchId: anchor.BN,
+    claim: any,
+    signer: Keypair = authority
+  ) =>
+    program.methods
+      .executeSettlement(batchId, claim)
+      .accountsPartial({
+        settlementAuthority: signer.publicKey,
+        matchRecord: deriveMatchRecordPDA(batchId, claim.matchId, program.programId)[0],
+        matchBatch: deriveMatchBatchPDA(batchId, program.programId)[0],
+        settlement: settlementAccounts(
+          user1,
+          claim.buyerOrderId.toNumber(),
+          user2,
+          claim.sellerOrderId.toNumber()
+        ),
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc({ commitment: "confirmed" });
+
+  // `signer` must be the market authority for the key to change
+  const setSettlementAuthority = (
+    newSettlementAuthority: PublicKey,
+    signer: Keypair = authority
+  ) =>
+    program.methods
+      .setSettlementAuthority(newSettlementAuthority)
+      .accountsPartial({
+        authority: signer.publicKey,
+        orderbookState: OrderbookPDA,
+      })
+      .signers([signer])
+      .rpc({ commitment: "confirmed" });
+
   before(async () => {
     console.log("\n========================================");
     console.log("Setting up test environment...");
@@ -188,7 +769,7 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
       console.log("\n--- Test 1.1.1: Initialize Program ---");
 
       // Generate x25519 keypair for backend encryption (needed for verification)
-      const backendSecretKey = x25519.utils.randomSecretKey();
+      backendSecretKey = x25519.utils.randomSecretKey();
       const backendPublicKey = x25519.getPublicKey(backendSecretKey);
 
       // Check if account already exists
@@ -201,7 +782,17 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
       if (!accountAlreadyExists) {
         // Initialize program
         const tx = await program.methods
-          .initialize(Array.from(backendPublicKey), baseMint, quoteMint)
+          .initialize(Array.from(backendPublicKey), baseMint, quoteMint, {
+            tickSize: new anchor.BN(1),
+            selfTradePolicy: 0, // cancel the resting order
+            priceRule: 0, // midpoint
+            matchingMode: 0, // continuous
+            allocation: 0, // price-time
+            minAllocation: new anchor.BN(0),
+            tradeTape: 0, // off
+            tapeDelay: new anchor.BN(0),
+            settlementMode: 0, // settlement bot
+          }, authority.publicKey) // settlement authority
           .accountsPartial({
             authority: authority.publicKey,
             orderBookState: OrderbookPDA,
@@ -479,19 +1070,79 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
       }
       expect(initOrderBookCompDefSig).to.exist;
 
+      let initBookPageCompDefSig;
+      try {
+        initBookPageCompDefSig = await initInitBookPageCompDef(
+          program,
+          authority,
+          false,
+          false
+        );
+      } catch (error) {
+        if (error.message.includes("already in use")) {
+          console.log("Init book page comp def already exists, skipping...");
+          initBookPageCompDefSig = "already_exists";
+        } else {
+          throw error;
+        }
+      }
+      expect(initBookPageCompDefSig).to.exist;
+
+      // The order's home page has to exist before it can be submitted
+      const [bookPagePDA] = deriveBookPagePDA(0, program.programId);
+      if (!(await accountExists(provider, bookPagePDA))) {
+        const bookPageComputationOffset = new anchor.BN(randomBytes(8), "hex");
+        await program.methods
+          .initBookPage(bookPageComputationOffset, 0)
+          .accountsPartial({
+            computationAccount: getComputationAccAddress(
+              program.programId,
+              bookPageComputationOffset
+            ),
+            authority: authority.publicKey,
+            signPdaAccount: deriveSignerAccountPDA(program.programId),
+            poolAccount: deriveArciumFeePoolAccountAddress(),
+            clusterAccount: arciumEnv.arciumClusterPubkey,
+            mxeAccount: getMXEAccAddress(program.programId),
+            mempoolAccount: getMempoolAccAddress(program.programId),
+            executingPool: getExecutingPoolAccAddress(program.programId),
+            compDefAccount: getCompDefAccAddress(
+              program.programId,
+              Buffer.from(getCompDefAccOffset("init_book_page")).readUInt32LE()
+            ),
+            clockAccount: getClockAccAddress(),
+            systemProgram: SystemProgram.programId,
+            arciumProgram: getArciumProgramId(),
+            orderbookState: OrderbookPDA,
+            bookPage: bookPagePDA,
+          })
+          .signers([authority])
+          .rpc({ commitment: "confirmed" });
+
+        await awaitComputationFinalization(
+          provider,
+          bookPageComputationOffset,
+          program.programId,
+          "confirmed"
+        );
+      }
+      const bookPage = await program.account.orderBookPage.fetch(bookPagePDA);
+      expect(bookPage.isReady).to.be.true;
+
       // 1. Setup encryption
       const { publicKey, cipher } = await setupUserEncryption(
         provider,
         program.programId
       );
 
+      // A buy is collateralized by the quote vault
       const [vaultPDA] = deriveVaultPDA(
-        baseMint,
+        quoteMint,
         user1.publicKey,
         program.programId
       );
       const [vaultStatePDA] = deriveVaultStatePDA(
-        baseMint,
+        quoteMint,
         user1.publicKey,
         program.programId
       );
@@ -501,7 +1152,7 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
         .initializeVault()
         .accountsPartial({
           user: user1.publicKey,
-          mint: baseMint,
+          mint: quoteMint,
           vault: vaultPDA,
           vaultState: vaultStatePDA,
           vaultAuthority: vaultAuthorityPDA,
@@ -513,15 +1164,15 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
       console.log("Vault initialized");
 
       console.log("user1", user1.publicKey.toBase58());
-      console.log("baseMint", baseMint.toBase58());
-      console.log("user1token1ATA", user1token1ATA.toBase58());
+      console.log("quoteMint", quoteMint.toBase58());
+      console.log("user1token2ATA", user1token2ATA.toBase58());
 
       // then deposit to the vault
       await program.methods
         .depositToVault(new BN(100))
         .accountsPartial({
           user: user1.publicKey,
-          userTokenAccount: user1token1ATA,
+          userTokenAccount: user1token2ATA,
           vault: vaultPDA,
         })
         .signers([user1])
@@ -587,7 +1238,8 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
 
       const User1Nonce = randomBytes(16);
       const User1Ciphertext = User1Cipher.encrypt(
-        [BigInt(amount), BigInt(price)],
+        // amount, price, iceberg slice, stop trigger, minimum fill
+        [BigInt(amount), BigInt(price), BigInt(0), BigInt(0), BigInt(0)],
         User1Nonce
       );
 
@@ -598,8 +1250,18 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
         .submitOrder(
           Array.from(User1Ciphertext[0]),
           Array.from(User1Ciphertext[1]),
+          Array.from(User1Ciphertext[2]),
+          Array.from(User1Ciphertext[3]),
+          Array.from(User1Ciphertext[4]),
           Array.from(User1PublicKey),
           0, // buy
+          0, // limit
+          0, // good-till-cancelled
+          0, // not post-only
+          0, // not a stop order
+          0, // not pegged
+          new anchor.BN(0), // no expiry
+          new anchor.BN(amount * price), // lock quantity * limit of quote
           submitOrderComputationOffset,
           new anchor.BN(orderId),
           new anchor.BN(deserializeLE(User1Nonce).toString())
@@ -625,11 +1287,12 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
           clockAccount: getClockAccAddress(),
           systemProgram: SystemProgram.programId, 
           arciumProgram: getArciumProgramId(),        
-          baseMint: baseMint,
+          mint: quoteMint,
           vault: vaultPDA,
           orderAccount: orderAccountPDA,
           vaultState: vaultStatePDA,
           orderbookState: OrderbookPDA,
+          bookPage: bookPagePDA,
         })
         .signers([user1])
         .rpc({ commitment: "confirmed" });
@@ -674,12 +1337,10 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
       // Test pubkey chunking
       const testPubkey = user1.publicKey.toBuffer();
 
-      // Split into 4x u64 chunks
+      // Split into 2x little-endian u128 chunks, as the program does
       const chunks = [
-        BigInt("0x" + testPubkey.slice(0, 8).toString("hex")),
-        BigInt("0x" + testPubkey.slice(8, 16).toString("hex")),
-        BigInt("0x" + testPubkey.slice(16, 24).toString("hex")),
-        BigInt("0x" + testPubkey.slice(24, 32).toString("hex")),
+        BigInt("0x" + Buffer.from(testPubkey.subarray(0, 16)).reverse().toString("hex")),
+        BigInt("0x" + Buffer.from(testPubkey.subarray(16, 32)).reverse().toString("hex")),
       ];
 
       console.log("Original pubkey:", user1.publicKey.toBase58());
@@ -690,16 +1351,15 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
 
       // Reconstruct
       const reconstructed = Buffer.concat([
-        Buffer.from(chunks[0].toString(16).padStart(16, "0"), "hex"),
-        Buffer.from(chunks[1].toString(16).padStart(16, "0"), "hex"),
-        Buffer.from(chunks[2].toString(16).padStart(16, "0"), "hex"),
-        Buffer.from(chunks[3].toString(16).padStart(16, "0"), "hex"),
+        Buffer.from(chunks[0].toString(16).padStart(32, "0"), "hex").reverse(),
+        Buffer.from(chunks[1].toString(16).padStart(32, "0"), "hex").reverse(),
       ]);
 
       console.log(
         "Reconstructed pubkey:",
         new PublicKey(reconstructed).toBase58()
       );
+      expect(new PublicKey(reconstructed).equals(user1.publicKey)).to.be.true;
 
       console.log("✓ Pubkey chunking works correctly");
     });
@@ -800,4 +1460,977 @@ describe("Dark Pool Matching Engine - Core Functionality Tests", () => {
       console.log("  - Verify token transfers");
     });
   });
+
+  describe("Suite 1.7: Collateral and Settlement Safety", () => {
+    before(async () => {
+      for (const [circuit, method] of [
+        ["cancel_order", "initCancelOrderCompDef"],
+        ["reveal_settlement", "initRevealSettlementCompDef"],
+        ["verify_settlement", "initVerifySettlementCompDef"],
+      ]) {
+        try {
+          await initCompDef(program, authority, circuit, method, false, false);
+        } catch (error) {
+          if (!error.message.includes("already in use")) throw error;
+          console.log(`${circuit} comp def already exists, skipping...`);
+        }
+      }
+
+      // user1 buys with quote and receives base, user2 the other way round
+      await initializeVault(baseMint, user1);
+      await initializeVault(baseMint, user2);
+      await initializeVault(quoteMint, user2);
+      await deposit(quoteMint, user1, user1token2ATA, 1000);
+      await deposit(baseMint, user2, user2token1ATA, 1000);
+
+      user1Encryption = await setupUserEncryption(provider, program.programId);
+      user2Encryption = await setupUserEncryption(provider, program.programId);
+    });
+
+    it("Test 1.7.1: Should refuse to reuse an order ID", async () => {
+      console.log("\n--- Test 1.7.1: Reused Order ID ---");
+
+      // Order 12 was submitted by Suite 1.3 and still rests in the book
+      const original = await fetchOrder(12, user1);
+      const lockedBefore = await fetchLocked(quoteMint, user1);
+
+      let error;
+      try {
+        await submit(user1, user1Encryption, 12, 0, 1, 1);
+      } catch (e) {
+        error = e;
+      }
+      expect(error, "resubmitting order 12 should fail").to.exist;
+      expect(error.message).to.include("already in use");
+
+      // The original order and its collateral are untouched
+      const order = await fetchOrder(12, user1);
+      expect(order.lockedAmount.toNumber()).to.equal(original.lockedAmount.toNumber());
+      expect(order.timestamp.toNumber()).to.equal(original.timestamp.toNumber());
+      expect(await fetchLocked(quoteMint, user1)).to.equal(lockedBefore);
+
+      // and it still cancels normally, which also clears the book for the
+      // tests below
+      await cancel(user1, 12);
+      const cancelled = await fetchOrder(12, user1);
+      expect(cancelled.status).to.equal(2);
+      expect(cancelled.lockedAmount.toNumber()).to.equal(0);
+      expect(await fetchLocked(quoteMint, user1)).to.equal(
+        lockedBefore - original.lockedAmount.toNumber()
+      );
+    });
+
+    it("Test 1.7.2: Should keep collateral owed to an unsettled fill locked on cancel", async () => {
+      console.log("\n--- Test 1.7.2: Cancel With Pending Fill ---");
+      await setSettlementMode(1);
+
+      const baseline = await fetchLocked(quoteMint, user1);
+      await submit(user1, user1Encryption, 101, 0, 10, 5); // locks 50
+      await submit(user2, user2Encryption, 201, 1, 4, 5);
+      const batchId = await triggerMatching(); // 4 @ 5 trades, owing 20
+
+      await cancel(user1, 101);
+      let buy = await fetchOrder(101, user1);
+      expect(buy.status).to.equal(2);
+      expect(buy.lockedAmount.toNumber()).to.equal(20, "the fill's quote stays locked");
+      expect(await fetchLocked(quoteMint, user1)).to.equal(baseline + 20);
+
+      const batch = await revealSettlement(batchId);
+      expect(batch.numSettlements).to.equal(1);
+      expect(batch.settlements[0].buyerFilled).to.be.false;
+      expect(batch.settlements[0].buyerOwed.toNumber()).to.equal(20);
+
+      await settleMatch(batchId, 0, 101, 201);
+      buy = await fetchOrder(101, user1);
+      expect(buy.status).to.equal(2, "a cancelled order stays cancelled");
+      expect(buy.lockedAmount.toNumber()).to.equal(0);
+      expect(buy.spentAmount.toNumber()).to.equal(20);
+      expect(await fetchLocked(quoteMint, user1)).to.equal(baseline);
+
+      const sell = await fetchOrder(201, user2);
+      expect(sell.status).to.equal(4);
+      expect(sell.lockedAmount.toNumber()).to.equal(0);
+    });
+
+    it("Test 1.7.3: Should keep collateral owed to an unsettled fill locked on eviction", async () => {
+      console.log("\n--- Test 1.7.3: Finalize Eviction With Pending Fill ---");
+
+      const baseline = await fetchLocked(quoteMint, user1);
+      await submit(user1, user1Encryption, 102, 0, 10, 5, { timeInForce: 1 }); // locks 50
+      await submit(user2, user2Encryption, 202, 1, 4, 5);
+      const batchId = await triggerMatching(); // 4 @ 5 trades, the rest expires
+
+      const evicted = await fetchBatch(batchId);
+      const index = evicted.evictedOrderIds.findIndex(
+        (id, i) =>
+          i < evicted.numEvictions &&
+          id.toNumber() === 102 &&
+          evicted.evictedOwners[i].equals(user1.publicKey)
+      );
+      expect(index).to.be.at.least(0, "the IOC remainder should be evicted");
+      expect(evicted.evictedOwed[index].toNumber()).to.equal(20);
+
+      await program.methods
+        .finalizeEvictedOrder(batchId, index)
+        .accountsPartial({
+          payer: authority.publicKey,
+          matchBatch: deriveMatchBatchPDA(batchId, program.programId)[0],
+          orderAccount: orderPDA(102, user1),
+          orderbookState: OrderbookPDA,
+          vaultState: deriveVaultStatePDA(quoteMint, user1.publicKey, program.programId)[0],
+        })
+        .signers([authority])
+        .rpc({ commitment: "confirmed" });
+
+      let buy = await fetchOrder(102, user1);
+      expect(buy.status).to.equal(5);
+      expect(buy.lockedAmount.toNumber()).to.equal(20, "the fill's quote stays locked");
+      expect(await fetchLocked(quoteMint, user1)).to.equal(baseline + 20);
+
+      await revealSettlement(batchId);
+      await settleMatch(batchId, 0, 102, 202);
+      buy = await fetchOrder(102, user1);
+      expect(buy.status).to.equal(5);
+      expect(buy.lockedAmount.toNumber()).to.equal(0);
+      expect(await fetchLocked(quoteMint, user1)).to.equal(baseline);
+    });
+
+    it("Test 1.7.4: Should settle fills of one order in any order", async () => {
+      console.log("\n--- Test 1.7.4: Out-of-Order Settlement ---");
+
+      const baseline = await fetchLocked(quoteMint, user1);
+      await submit(user1, user1Encryption, 103, 0, 10, 5); // locks 50
+      await submit(user2, user2Encryption, 203, 1, 4, 5);
+      await submit(user2, user2Encryption, 204, 1, 6, 5);
+      const batchId = await triggerMatching();
+
+      const batch = await revealSettlement(batchId);
+      expect(batch.numSettlements).to.equal(2);
+      const entries = batch.settlements.slice(0, 2);
+      const last = entries.findIndex((entry) => entry.buyerFilled);
+      const first = 1 - last;
+      expect(last).to.be.at.least(0, "one fill should complete the buy");
+      expect(entries[last].buyerOwed.toNumber()).to.equal(50);
+
+      // The completing fill settles first and must leave the other fill's
+      // quote locked
+      await settleMatch(
+        batchId,
+        last,
+        103,
+        entries[last].sellerOrderId.toNumber()
+      );
+      let buy = await fetchOrder(103, user1);
+      expect(buy.status).to.equal(4);
+      expect(buy.lockedAmount.toNumber()).to.equal(
+        entries[first].quantity.toNumber() * 5
+      );
+
+      await settleMatch(
+        batchId,
+        first,
+        103,
+        entries[first].sellerOrderId.toNumber()
+      );
+      buy = await fetchOrder(103, user1);
+      expect(buy.status).to.equal(4);
+      expect(buy.lockedAmount.toNumber()).to.equal(0);
+      expect(buy.spentAmount.toNumber()).to.equal(50);
+      expect(await fetchLocked(quoteMint, user1)).to.equal(baseline);
+
+      await expectError(
+        settleMatch(batchId, first, 103, entries[first].sellerOrderId.toNumber()),
+        "AlreadySettled"
+      );
+    });
+
+    it("Test 1.7.5: Should not let a claim change while it is being verified", async () => {
+      console.log("\n--- Test 1.7.5: Re-commit During Verification ---");
+      await setSettlementMode(0);
+
+      await submit(user1, user1Encryption, 104, 0, 3, 5); // locks 15
+      await submit(user2, user2Encryption, 205, 1, 3, 5);
+      const batchId = await triggerMatching();
+
+      // Match IDs restart at 0 in every batch
+      const claim = {
+        matchId: new BN(0),
+        buyer: user1.publicKey,
+        seller: user2.publicKey,
+        buyerOrderId: new BN(104),
+        sellerOrderId: new BN(205),
+        quantity: new BN(3),
+        executionPrice: new BN(5),
+        buyerFilled: true,
+        sellerFilled: true,
+        buyerOwed: new BN(15),
+        sellerOwed: new BN(3),
+      };
+      const forged = {
+        ...claim,
+        quantity: new BN(1),
+        buyerOwed: new BN(5),
+        sellerOwed: new BN(1),
+      };
+      const [matchRecordPDA] = deriveMatchRecordPDA(
+        batchId,
+        claim.matchId,
+        program.programId
+      );
+
+      // Swapping the claim while the forged one is checked must not work
+      const forgedOffset = await commitSettlement(batchId, forged);
+      await expectError(commitSettlement(batchId, claim), "SettlementVerificationPending");
+
+      await awaitFinalization(forgedOffset);
+      let record = await program.account.matchRecord.fetch(matchRecordPDA);
+      expect(record.verificationPending).to.be.false;
+      expect(record.isVerified).to.be.false;
+      await expectError(executeSettlement(batchId, forged), "SettlementNotVerified");
+
+      // Once the check is done the right claim can be committed
+      await awaitFinalization(await commitSettlement(batchId, claim));
+      record = await program.account.matchRecord.fetch(matchRecordPDA);
+      expect(record.isVerified).to.be.true;
+
+      // Only the verified claim moves funds, and it cannot be replaced
+      await expectError(executeSettlement(batchId, forged), "SettlementNotVerified");
+      await expectError(commitSettlement(batchId, forged), "AlreadySettled");
+
+      await executeSettlement(batchId, claim);
+      const buy = await fetchOrder(104, user1);
+      expect(buy.status).to.equal(4);
+      expect(buy.lockedAmount.toNumber()).to.equal(0);
+      const sell = await fetchOrder(205, user2);
+      expect(sell.status).to.equal(4);
+      expect(sell.lockedAmount.toNumber()).to.equal(0);
+    });
+
+    it("Test 1.7.6: Should settle a batch only through the mode it was matched in", async () => {
+      console.log("\n--- Test 1.7.6: Settlement Mode Change ---");
+      await setSettlementMode(1);
+
+      await submit(user1, user1Encryption, 105, 0, 2, 5); // locks 10
+      await submit(user2, user2Encryption, 206, 1, 2, 5);
+      const batchId = await triggerMatching();
+      expect((await fetchBatch(batchId)).settlementMode).to.equal(1);
+
+      // Switching the market to the bot does not open the bot path for the
+      // batch already matched
+      await setSettlementMode(0);
+      const claim = {
+        matchId: new BN(0),
+        buyer: user1.publicKey,
+        seller: user2.publicKey,
+        buyerOrderId: new BN(105),
+        sellerOrderId: new BN(206),
+        quantity: new BN(2),
+        executionPrice: new BN(5),
+        buyerFilled: true,
+        sellerFilled: true,
+        buyerOwed: new BN(10),
+        sellerOwed: new BN(2),
+      };
+      await expectError(commitSettlement(batchId, claim), "OffChainSettlementDisabled");
+
+      await revealSettlement(batchId);
+      await settleMatch(batchId, 0, 105, 206);
+      const buy = await fetchOrder(105, user1);
+      expect(buy.status).to.equal(4);
+      expect(buy.lockedAmount.toNumber()).to.equal(0);
+    });
+
+    it("Test 1.7.7: Should refuse a second computation while the book is in flight", async () => {
+      console.log("\n--- Test 1.7.7: Book In Flight ---");
+
+      const offset = await queueSubmit(user1, user1Encryption, 106, 0, 1, 5);
+      let state = await getOrderBookState(program);
+      expect(state.pendingComputation.equals(PublicKey.default)).to.be.false;
+
+      // The second submission would have read the book without the first
+      await expectError(
+        queueSubmit(user2, user2Encryption, 207, 1, 1, 9),
+        "OrderBookBusy"
+      );
+      expect(await accountExists(provider, orderPDA(207, user2))).to.be.false;
+
+      await awaitFinalization(offset);
+      state = await getOrderBookState(program);
+      expect(state.pendingComputation.equals(PublicKey.default)).to.be.true;
+
+      // Both orders are in the book once they went in one after the other
+      await submit(user2, user2Encryption, 207, 1, 1, 9);
+      await cancel(user1, 106);
+      await cancel(user2, 207);
+      expect((await fetchOrder(106, user1)).status).to.equal(2);
+      expect((await fetchOrder(207, user2)).status).to.equal(2);
+    });
+  });
+
+  // Each test leaves the book empty; fills are read back by decrypting the
+  // batch with the backend key
+  describe("Suite 1.8: Order Types and Matching Rules", () => {
+    before(async () => {
+      for (const [circuit, method] of [
+        ["amend_order", "initAmendOrderCompDef"],
+        ["reveal_fills", "initRevealFillsCompDef"],
+        ["reveal_tape", "initRevealTapeCompDef"],
+      ]) {
+        try {
+          await initCompDef(program, authority, circuit, method, false, false);
+        } catch (error) {
+          if (!error.message.includes("already in use")) throw error;
+          console.log(`${circuit} comp def already exists, skipping...`);
+        }
+      }
+
+      // Both users trade both sides from here on
+      await deposit(quoteMint, user1, user1token2ATA, 10000);
+      await deposit(baseMint, user1, user1token1ATA, 10000);
+      await deposit(baseMint, user2, user2token1ATA, 10000);
+      await deposit(quoteMint, user2, user2token2ATA, 10000);
+    });
+
+    it("Test 1.8.1: Should keep time priority on a reduction and reset it on a price change", async () => {
+      console.log("\n--- Test 1.8.1: Amend ---");
+
+      const baseline = await fetchLocked(quoteMint, user1);
+      await submit(user1, user1Encryption, 301, 0, 4, 5); // locks 20
+      await submit(user1, user1Encryption, 302, 0, 4, 6); // locks 24
+      await submit(user1, user1Encryption, 303, 0, 4, 5); // locks 20
+
+      // A pure reduction keeps 301 first and frees what it no longer needs
+      await amend(user1, user1Encryption, 301, 2, 5, 10);
+      const reduced = await fetchOrder(301, user1);
+      expect(reduced.lockedAmount.toNumber()).to.equal(10);
+      expect(reduced.lastAmended.toNumber()).to.be.greaterThan(0);
+
+      // A price change puts 302 behind 303 at their common price
+      await amend(user1, user1Encryption, 302, 4, 5, 20);
+      expect((await fetchOrder(302, user1)).lockedAmount.toNumber()).to.equal(20);
+      expect(await fetchLocked(quoteMint, user1)).to.equal(baseline + 50);
+
+      // An amendment its lock does not cover is refused
+      await amend(user1, user1Encryption, 303, 4, 9, 20);
+      const refused = await fetchOrder(303, user1);
+      expect(refused.lastAmended.toNumber()).to.equal(0);
+      expect(refused.lockedAmount.toNumber()).to.equal(20);
+
+      await submit(user2, user2Encryption, 501, 1, 2, 5);
+      await submit(user2, user2Encryption, 502, 1, 4, 5);
+      const batchId = await triggerMatching();
+
+      const matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([
+        [301, 501, 2, 5],
+        [303, 502, 4, 5],
+      ]);
+
+      await cancel(user1, 302);
+      const cancelled = await fetchOrder(302, user1);
+      expect(cancelled.status).to.equal(2);
+      expect(cancelled.lockedAmount.toNumber()).to.equal(0);
+    });
+
+    it("Test 1.8.2: Should sweep a market order up to its protection limit and drop the rest", async () => {
+      console.log("\n--- Test 1.8.2: Market Order ---");
+
+      await submit(user2, user2Encryption, 511, 1, 2, 5);
+      await submit(user2, user2Encryption, 512, 1, 2, 7);
+      await submit(user1, user1Encryption, 311, 0, 3, 6, { market: true }); // locks 18
+      const batchId = await triggerMatching();
+
+      // The market buy takes the sell's price and stops short of 7
+      const matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([[311, 511, 2, 5]]);
+      expect(matches[0].buyerOwed).to.equal(10);
+
+      // Its remainder never rests
+      const eviction = await finalizeEviction(batchId, 311, user1);
+      expect(eviction.reason).to.equal(1);
+      expect(eviction.owed).to.equal(10);
+      const buy = await fetchOrder(311, user1);
+      expect(buy.status).to.equal(5);
+      expect(buy.lockedAmount.toNumber()).to.equal(10, "the fill's quote stays locked");
+
+      await cancel(user2, 512);
+    });
+
+    it("Test 1.8.3: Should fill a fill-or-kill order completely or not at all", async () => {
+      console.log("\n--- Test 1.8.3: Time in Force ---");
+
+      const baseline = await fetchLocked(quoteMint, user1);
+      await submit(user2, user2Encryption, 521, 1, 2, 5);
+      await submit(user1, user1Encryption, 321, 0, 3, 5, { timeInForce: 2 }); // locks 15
+      await submit(user1, user1Encryption, 322, 0, 2, 5, { timeInForce: 2 }); // locks 10
+      await submit(user1, user1Encryption, 323, 0, 1, 5, { timeInForce: 1 }); // locks 5
+      const batchId = await triggerMatching();
+
+      // 321 comes first but only 2 of its 3 are available, so it is killed
+      // without trading; 322 then takes the whole sell and the IOC order
+      // finds nothing left
+      const matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([[322, 521, 2, 5]]);
+      expect(matches[0].buyerFilled).to.be.true;
+
+      for (const orderId of [321, 323]) {
+        const eviction = await finalizeEviction(batchId, orderId, user1);
+        expect(eviction.reason).to.equal(1);
+        expect(eviction.owed).to.equal(0);
+        const buy = await fetchOrder(orderId, user1);
+        expect(buy.status).to.equal(5);
+        expect(buy.lockedAmount.toNumber()).to.equal(0);
+      }
+      expect(await fetchLocked(quoteMint, user1)).to.equal(
+        baseline + 10,
+        "only the filled order's quote stays locked"
+      );
+    });
+
+    it("Test 1.8.4: Should reject or reprice a post-only order that would cross", async () => {
+      console.log("\n--- Test 1.8.4: Post-Only ---");
+
+      const baseline = await fetchLocked(quoteMint, user1);
+      await submit(user2, user2Encryption, 531, 1, 2, 6);
+
+      // Rejected with its own status, and its lock is released
+      await awaitFinalization(
+        await queueSubmit(user1, user1Encryption, 331, 0, 2, 6, { postOnly: 1 })
+      );
+      const rejected = await fetchOrder(331, user1);
+      expect(rejected.status).to.equal(6);
+      expect(rejected.lockedAmount.toNumber()).to.equal(0);
+      expect(await fetchLocked(quoteMint, user1)).to.equal(baseline);
+
+      // Slid to one tick under the best sell instead of taking it
+      await submit(user1, user1Encryption, 332, 0, 2, 7, { postOnly: 2 }); // locks 14
+      await submit(user2, user2Encryption, 532, 1, 2, 5);
+      const batchId = await triggerMatching();
+
+      // At its submitted 7 the buy would have met 532 at a midpoint of 6
+      const matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([[332, 532, 2, 5]]);
+
+      await cancel(user2, 531);
+    });
+
+    it("Test 1.8.5: Should fill an iceberg one slice at a time and requeue each refill", async () => {
+      console.log("\n--- Test 1.8.5: Iceberg ---");
+
+      await submit(user1, user1Encryption, 341, 0, 6, 5, { displayAmount: 2 }); // locks 30
+      await submit(user1, user1Encryption, 342, 0, 2, 5); // locks 10
+      await submit(user2, user2Encryption, 541, 1, 6, 5);
+      const batchId = await triggerMatching();
+
+      // Every fill of 341 is one slice, and its refill queues behind 342
+      const matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([
+        [341, 541, 2, 5],
+        [342, 541, 2, 5],
+        [341, 541, 2, 5],
+      ]);
+      expect(matches[0].buyerFilled).to.be.false;
+      expect(matches[2].buyerFilled).to.be.false;
+      expect(matches[2].buyerOwed).to.equal(20);
+      expect(matches[2].sellerFilled).to.be.true;
+
+      // The last slice of the reserve is still in the book
+      await cancel(user1, 341);
+      const buy = await fetchOrder(341, user1);
+      expect(buy.status).to.equal(2);
+      expect(buy.lockedAmount.toNumber()).to.equal(20, "the fills' quote stays locked");
+    });
+
+    it("Test 1.8.6: Should release a stop once the last price reaches its trigger", async () => {
+      console.log("\n--- Test 1.8.6: Stop Orders ---");
+
+      // The last trade printed at 5, below the trigger
+      await submit(user1, user1Encryption, 351, 0, 2, 8, { stopTrigger: 6 }); // locks 16
+      await submit(user1, user1Encryption, 352, 0, 1, 6);
+      await submit(user2, user2Encryption, 551, 1, 1, 6);
+      await submit(user2, user2Encryption, 552, 1, 2, 7);
+      let batchId = await triggerMatching();
+
+      // The print at 6 releases the stop, which trades in the same run
+      let matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([
+        [352, 551, 1, 6],
+        [351, 552, 2, 7],
+      ]);
+
+      // A stop already triggered by the stored last price (7) is released
+      // at the start of the next run, before any trade
+      await submit(user1, user1Encryption, 353, 1, 1, 7, { stopTrigger: 7 });
+      await submit(user2, user2Encryption, 553, 0, 1, 7);
+      batchId = await triggerMatching();
+
+      matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([[553, 353, 1, 7]]);
+    });
+
+    it("Test 1.8.7: Should price pegged orders at the midpoint and rank them by time", async () => {
+      console.log("\n--- Test 1.8.7: Midpoint Pegs ---");
+
+      // Plain orders at 5 and 9 put the midpoint at 7
+      await submit(user1, user1Encryption, 361, 0, 1, 5);
+      await submit(user2, user2Encryption, 561, 1, 1, 9);
+      await submit(user1, user1Encryption, 362, 0, 1, 8, { pegged: true }); // limit 8
+      await submit(user1, user1Encryption, 363, 0, 1, 10, { pegged: true }); // limit 10
+      await submit(user2, user2Encryption, 562, 1, 1, 6, { pegged: true }); // limit 6
+      const batchId = await triggerMatching();
+
+      // The older peg fills first even though the newer one would pay more
+      const matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([[362, 562, 1, 7]]);
+
+      await cancel(user1, 361);
+      await cancel(user1, 363);
+      await cancel(user2, 561);
+    });
+
+    it("Test 1.8.8: Should skip a counterparty too small for the minimum fill", async () => {
+      console.log("\n--- Test 1.8.8: Minimum Fill ---");
+
+      await submit(user2, user2Encryption, 571, 1, 2, 5);
+      await submit(user2, user2Encryption, 572, 1, 6, 5);
+      await submit(user1, user1Encryption, 371, 0, 10, 5, { minFill: 5 }); // locks 50
+      const batchId = await triggerMatching();
+
+      // 571 is first in line but its 2 are below the buy's minimum of 5;
+      // what is left of the buy afterwards (4) is still more than 571 has
+      const matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([[371, 572, 6, 5]]);
+
+      await cancel(user1, 371);
+      await cancel(user2, 571);
+      expect((await fetchOrder(571, user2)).lockedAmount.toNumber()).to.equal(0);
+    });
+
+    it("Test 1.8.9: Should cancel the side the self-trade policy names instead of trading", async () => {
+      console.log("\n--- Test 1.8.9: Self-Trade Prevention ---");
+
+      // Policy 0 cancels the resting order, here user1's older sell
+      await submit(user1, user1Encryption, 381, 1, 2, 5); // locks 2 base
+      await submit(user1, user1Encryption, 382, 0, 2, 5);
+      await submit(user2, user2Encryption, 581, 1, 2, 5);
+      let batchId = await triggerMatching();
+
+      let matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([[382, 581, 2, 5]]);
+
+      let eviction = await finalizeEviction(batchId, 381, user1);
+      expect(eviction.reason).to.equal(2);
+      const sell = await fetchOrder(381, user1);
+      expect(sell.status).to.equal(2);
+      expect(sell.lockedAmount.toNumber()).to.equal(0);
+
+      // Policy 1 cancels the incoming order and leaves the resting one
+      await updateConfig({ selfTradePolicy: 1 });
+      await submit(user1, user1Encryption, 383, 1, 1, 5);
+      await submit(user1, user1Encryption, 384, 0, 1, 5);
+      batchId = await triggerMatching();
+
+      matches = await decryptBatch(batchId);
+      expect(matches).to.be.empty;
+      eviction = await finalizeEviction(batchId, 384, user1);
+      expect(eviction.reason).to.equal(2);
+      expect((await fetchOrder(384, user1)).status).to.equal(2);
+
+      await cancel(user1, 383);
+      await updateConfig({ selfTradePolicy: 0 });
+    });
+
+    it("Test 1.8.10: Should evict expired orders from the top of the book and from the page", async () => {
+      console.log("\n--- Test 1.8.10: Good-Till-Time Expiry ---");
+
+      for (const orderId of [391, 392, 393, 394]) {
+        await submit(user1, user1Encryption, orderId, 0, 1, 4);
+      }
+      const expiry = Math.floor(Date.now() / 1000) + 30;
+      // The best buy, and one that finds the top full and is parked on page 0
+      await submit(user1, user1Encryption, 395, 0, 1, 6, { expiry });
+      await submit(user1, user1Encryption, 396, 0, 1, 3, { expiry });
+      const [bookPagePDA] = deriveBookPagePDA(0, program.programId);
+      expect((await program.account.orderBookPage.fetch(bookPagePDA)).buyCount).to.equal(1);
+
+      await submit(user2, user2Encryption, 591, 1, 1, 6);
+      const wait = expiry + 2 - Math.floor(Date.now() / 1000);
+      if (wait > 0) await sleep(wait * 1000);
+      const batchId = await triggerMatching();
+
+      // 395 would have met the sell at 6
+      expect(await decryptBatch(batchId)).to.be.empty;
+      for (const orderId of [395, 396]) {
+        const eviction = await finalizeEviction(batchId, orderId, user1);
+        expect(eviction.reason).to.equal(3);
+        const buy = await fetchOrder(orderId, user1);
+        expect(buy.status).to.equal(5);
+        expect(buy.lockedAmount.toNumber()).to.equal(0);
+      }
+      expect((await program.account.orderBookPage.fetch(bookPagePDA)).buyCount).to.equal(0);
+
+      for (const orderId of [391, 392, 393, 394]) {
+        await cancel(user1, orderId);
+      }
+      await cancel(user2, 591);
+    });
+
+    it("Test 1.8.11: Should price fills by the market's execution-price rule", async () => {
+      console.log("\n--- Test 1.8.11: Execution-Price Rule ---");
+
+      // The sell is older, so it rests and the buy is the aggressor; the
+      // midpoint would be 5 either way
+      const cases = [
+        { priceRule: 1, buyId: 401, sellId: 601, price: 4 }, // resting price
+        { priceRule: 2, buyId: 402, sellId: 602, price: 6 }, // aggressor price
+      ];
+      for (const { priceRule, buyId, sellId, price } of cases) {
+        await updateConfig({ priceRule });
+        await submit(user2, user2Encryption, sellId, 1, 2, 4);
+        await submit(user1, user1Encryption, buyId, 0, 2, 6); // locks 12
+        const batchId = await triggerMatching();
+
+        const matches = await decryptBatch(batchId);
+        expect(
+          matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+        ).to.deep.equal([[buyId, sellId, 2, price]]);
+        expect(matches[0].buyerOwed).to.equal(2 * price);
+      }
+
+      await updateConfig({ priceRule: 0 });
+    });
+
+    it("Test 1.8.12: Should clear an auction run at the single volume-maximizing price", async () => {
+      console.log("\n--- Test 1.8.12: Uniform-Price Auction ---");
+      await updateConfig({ matchingMode: 1 });
+
+      await submit(user1, user1Encryption, 411, 0, 3, 8); // locks 24
+      await submit(user1, user1Encryption, 412, 0, 2, 5); // locks 10
+      await submit(user2, user2Encryption, 611, 1, 2, 4);
+      await submit(user2, user2Encryption, 612, 1, 2, 5);
+      await submit(user2, user2Encryption, 613, 1, 1, 8);
+      const batchId = await triggerMatching();
+
+      // 4 trade at 5, against 2 at 4 and 3 at 8; continuous matching would
+      // have paired 411 with 611 at a midpoint of 6
+      const matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([
+        [411, 611, 2, 5],
+        [411, 612, 1, 5],
+        [412, 612, 1, 5],
+      ]);
+
+      await updateConfig({ matchingMode: 0 });
+      await cancel(user1, 412);
+      await cancel(user2, 613);
+    });
+
+    it("Test 1.8.13: Should split a level pro rata to resting size", async () => {
+      console.log("\n--- Test 1.8.13: Pro-Rata Allocation ---");
+      await updateConfig({ allocation: 1, minAllocation: new BN(0) });
+
+      await submit(user2, user2Encryption, 621, 1, 1, 5);
+      await submit(user2, user2Encryption, 622, 1, 3, 5);
+      await submit(user1, user1Encryption, 421, 0, 2, 5); // locks 10
+      const batchId = await triggerMatching();
+
+      // Shares of 2 over 1 and 3 round down to 0 and 1, and the remainder
+      // goes to the larger order; time priority would have split it 1 and 1
+      const matches = await decryptBatch(batchId);
+      expect(
+        matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])
+      ).to.deep.equal([[421, 622, 2, 5]]);
+
+      await updateConfig({ allocation: 0 });
+      await cancel(user2, 621);
+      await cancel(user2, 622);
+    });
+
+    it("Test 1.8.14: Should page orders past the top of the book and match them back in", async () => {
+      console.log("\n--- Test 1.8.14: Book Pages ---");
+
+      for (const orderId of [431, 432, 433, 434, 435, 436]) {
+        await submit(user1, user1Encryption, orderId, 0, 1, 5);
+      }
+      // The sixth buy found the top full and was parked on page 0
+      const [bookPagePDA] = deriveBookPagePDA(0, program.programId);
+      expect((await program.account.orderBookPage.fetch(bookPagePDA)).buyCount).to.equal(1);
+
+      // The run promotes it into the slot the cancel frees, then fills all
+      // five buys, more than the old limit of 3 matches per batch
+      await cancel(user1, 431);
+      await submit(user2, user2Encryption, 631, 1, 5, 5);
+      const batchId = await triggerMatching();
+
+      const matches = await decryptBatch(batchId);
+      expect(matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity])).to.deep.equal([
+        [432, 631, 1],
+        [433, 631, 1],
+        [434, 631, 1],
+        [435, 631, 1],
+        [436, 631, 1],
+      ]);
+      expect(matches.map((m) => m.matchId)).to.deep.equal([0, 1, 2, 3, 4]);
+      expect((await program.account.orderBookPage.fetch(bookPagePDA)).buyCount).to.equal(0);
+    });
+
+    it("Test 1.8.15: Should reveal an order's own fills to its owner only", async () => {
+      console.log("\n--- Test 1.8.15: Reveal Fills ---");
+
+      await submit(user1, user1Encryption, 441, 0, 3, 5);
+      await submit(user2, user2Encryption, 641, 1, 1, 5);
+      await submit(user2, user2Encryption, 642, 1, 2, 5);
+      const batchId = await triggerMatching();
+
+      const eventPromise = awaitEvent("fillsRevealedEvent");
+      await revealFills(user1, batchId, 441);
+      const event = await eventPromise;
+
+      const receipt = await program.account.fillReceipt.fetch(
+        deriveFillReceiptPDA(batchId, new anchor.BN(441), user1.publicKey, program.programId)[0]
+      );
+      expect(receipt.isReady).to.be.true;
+      expect(receipt.encPubkey).to.deep.equal(Array.from(user1Encryption.publicKey));
+
+      // Only the owner's key opens it, and it holds both of 441's fills
+      const fills = decryptFills(user1Encryption, receipt.fillsNonce, receipt.fillsData);
+      expect(fills).to.deep.equal([
+        { matchId: 0, quantity: 1, executionPrice: 5 },
+        { matchId: 1, quantity: 2, executionPrice: 5 },
+      ]);
+
+      // The event carries the same ciphertexts for a listener
+      expect(event.batchId.eq(batchId)).to.be.true;
+      expect(event.orderId.toNumber()).to.equal(441);
+      expect(event.user.equals(user1.publicKey)).to.be.true;
+      expect(event.nonce.eq(receipt.fillsNonce)).to.be.true;
+      expect(decryptFills(user1Encryption, event.nonce, event.ciphertexts)).to.deep.equal(fills);
+
+      // The counterparty sees its own side of the first match only
+      await revealFills(user2, batchId, 641);
+      const counterparty = await program.account.fillReceipt.fetch(
+        deriveFillReceiptPDA(batchId, new anchor.BN(641), user2.publicKey, program.programId)[0]
+      );
+      expect(
+        decryptFills(user2Encryption, counterparty.fillsNonce, counterparty.fillsData)
+      ).to.deep.equal([{ matchId: 0, quantity: 1, executionPrice: 5 }]);
+
+      // A receipt is written once, and nobody reveals another user's order
+      await expectError(revealFills(user1, batchId, 441), "FillsAlreadyRevealed");
+      await expectError(revealFills(user2, batchId, 441, user1), "NotOrderOwner");
+    });
+
+    it("Test 1.8.16: Should keep every batch's result and emit it with the batch", async () => {
+      console.log("\n--- Test 1.8.16: Match Results ---");
+
+      await submit(user1, user1Encryption, 442, 0, 2, 5);
+      await submit(user2, user2Encryption, 643, 1, 2, 5);
+
+      const eventPromise = awaitEvent("matchResultEvent");
+      const batchId = await triggerMatching();
+      const event = await eventPromise;
+
+      // The event carries exactly what the batch account keeps
+      const batch = await fetchBatch(batchId);
+      expect(event.batchId.eq(batchId)).to.be.true;
+      expect(event.matchNonce.eq(batch.resultNonce)).to.be.true;
+      expect(event.matchCiphertexts).to.deep.equal(batch.resultData);
+      expect(event.timestamp.toNumber()).to.be.greaterThan(0);
+
+      const matches = await decryptMatches(event.matchNonce, event.matchCiphertexts);
+      expect(matches).to.deep.equal(await decryptBatch(batchId));
+      expect(matches.map((m) => [m.buyerOrderId, m.sellerOrderId, m.quantity, m.executionPrice])).to.deep.equal([
+        [442, 643, 2, 5],
+      ]);
+
+      // A later batch gets its own account, so earlier results stay readable
+      const earlier = await decryptBatch(batchId.subn(1));
+      expect(earlier.map((m) => [m.buyerOrderId, m.sellerOrderId])).to.deep.equal([
+        [441, 641],
+        [441, 642],
+      ]);
+    });
+
+    it("Test 1.8.17: Should publish a batch's trades only when the market opted in, and only after the delay", async () => {
+      console.log("\n--- Test 1.8.17: Trade Tape ---");
+
+      // Matched with the tape off: the batch stays private, even once the
+      // market opts in afterwards
+      await submit(user1, user1Encryption, 443, 0, 1, 5);
+      await submit(user2, user2Encryption, 644, 1, 1, 5);
+      const privateBatch = await triggerMatching();
+      await expectError(publishTape(privateBatch), "TradeTapeDisabled");
+
+      await updateConfig({ tradeTape: 1, tapeDelay: new anchor.BN(30) });
+      await expectError(publishTape(privateBatch), "TradeTapeDisabled");
+      expect((await fetchBatch(privateBatch)).tapePublished).to.be.false;
+
+      // Opted in, the batch waits out the delay before it can be published
+      await submit(user1, user1Encryption, 444, 0, 1, 5);
+      await submit(user2, user2Encryption, 645, 1, 1, 5);
+      const delayedBatch = await triggerMatching();
+      await expectError(publishTape(delayedBatch), "TradeTapeDelayNotElapsed");
+
+      const { timestamp } = await fetchBatch(delayedBatch);
+      const wait = timestamp.toNumber() + 31 - Math.floor(Date.now() / 1000);
+      if (wait > 0) await sleep(wait * 1000);
+
+      // The tape account is created by the first publish
+      const existing = await program.account.tradeTape.fetchNullable(
+        deriveTradeTapePDA(program.programId)[0]
+      );
+      const before = existing ? existing.totalEntries.toNumber() : 0;
+      const eventPromise = awaitEvent("tradeTapeEvent");
+      await publishTape(delayedBatch);
+      const event = await eventPromise;
+
+      expect(event.batchId.eq(delayedBatch)).to.be.true;
+      expect(event.count).to.equal(1);
+      expect(event.quantities[0].toNumber()).to.equal(1);
+      expect(event.prices[0].toNumber()).to.equal(5);
+      expect(event.timestamp.eq(timestamp)).to.be.true;
+
+      let tape = await fetchTape();
+      expect(tape.totalEntries.toNumber()).to.equal(before + 1);
+      let entry = tape.entries[(tape.head + 63) % 64];
+      expect(entry.batchId.eq(delayedBatch)).to.be.true;
+      expect([entry.quantity.toNumber(), entry.price.toNumber()]).to.deep.equal([1, 5]);
+      await expectError(publishTape(delayedBatch), "TradeTapeAlreadyPublished");
+
+      // Aggregated, a batch is one entry at its volume-weighted price:
+      // 1 at the midpoint 5 and 2 at 6 is 3 at 17/3, rounded down
+      await updateConfig({ tradeTape: 2, tapeDelay: new anchor.BN(0) });
+      await submit(user2, user2Encryption, 646, 1, 1, 4);
+      await submit(user2, user2Encryption, 647, 1, 2, 6);
+      await submit(user1, user1Encryption, 445, 0, 3, 6);
+      const aggregatedBatch = await triggerMatching();
+      expect((await decryptBatch(aggregatedBatch)).map((m) => [m.quantity, m.executionPrice])).to.deep.equal([
+        [1, 5],
+        [2, 6],
+      ]);
+      await publishTape(aggregatedBatch);
+
+      tape = await fetchTape();
+      expect(tape.totalEntries.toNumber()).to.equal(before + 2);
+      entry = tape.entries[(tape.head + 63) % 64];
+      expect(entry.batchId.eq(aggregatedBatch)).to.be.true;
+      expect([entry.quantity.toNumber(), entry.price.toNumber()]).to.deep.equal([3, 5]);
+
+      await updateConfig({ tradeTape: 0 });
+    });
+
+    it("Test 1.8.18: Should overwrite the oldest tape entries once the ring buffer is full", async () => {
+      console.log("\n--- Test 1.8.18: Tape Ring Buffer ---");
+
+      await updateConfig({ tradeTape: 1 });
+      const before = (await fetchTape()).totalEntries.toNumber();
+
+      // Nine batches of 8 one-lot trades each are 72 entries, 8 more than
+      // the tape holds, so the first batch's entries are all overwritten
+      const batchIds: anchor.BN[] = [];
+      for (let k = 0; k < 9; k++) {
+        for (const [j, amount] of [2, 2, 2, 1, 1].entries()) {
+          await submit(user1, user1Encryption, 460 + 10 * k + j, 0, amount, 5);
+        }
+        for (const [j, amount] of [1, 2, 2, 3].entries()) {
+          await submit(user2, user2Encryption, 660 + 10 * k + j, 1, amount, 5);
+        }
+        const batchId = await triggerMatching();
+        expect((await decryptBatch(batchId)).length).to.equal(8);
+        await publishTape(batchId);
+        batchIds.push(batchId);
+      }
+
+      const tape = await fetchTape();
+      const total = tape.totalEntries.toNumber();
+      expect(total).to.equal(before + 72);
+      expect(tape.head).to.equal(total % 64);
+      expect(tape.entries.some((e) => e.batchId.eq(batchIds[0]))).to.be.false;
+      expect(tape.entries.filter((e) => e.batchId.eq(batchIds[1])).length).to.equal(8);
+
+      // The newest batch sits just behind the head
+      const last = batchIds[batchIds.length - 1];
+      for (let i = 1; i <= 8; i++) {
+        const entry = tape.entries[(tape.head + 64 - i) % 64];
+        expect(entry.batchId.eq(last)).to.be.true;
+        expect([entry.quantity.toNumber(), entry.price.toNumber()]).to.deep.equal([1, 5]);
+      }
+
+      await updateConfig({ tradeTape: 0 });
+    });
+
+    it("Test 1.8.19: Should let only the market authority rotate the settlement key, and refuse the old key after", async () => {
+      console.log("\n--- Test 1.8.19: Settlement Authority ---");
+
+      // Pays for the match records it commits
+      const opsKey = Keypair.generate();
+      await airdrop(provider, opsKey.publicKey, 10 * LAMPORTS_PER_SOL);
+      expect(
+        (await getOrderBookState(program)).settlementAuthority.equals(authority.publicKey)
+      ).to.be.true;
+
+      await expectError(setSettlementAuthority(opsKey.publicKey, user1), "Unauthorized");
+
+      const eventPromise = awaitEvent("settlementAuthorityChangedEvent");
+      await setSettlementAuthority(opsKey.publicKey);
+      const event = await eventPromise;
+      expect(event.previous.equals(authority.publicKey)).to.be.true;
+      expect(event.new.equals(opsKey.publicKey)).to.be.true;
+      expect(
+        (await getOrderBookState(program)).settlementAuthority.equals(opsKey.publicKey)
+      ).to.be.true;
+
+      await setSettlementMode(0);
+      await submit(user1, user1Encryption, 446, 0, 2, 5); // locks 10
+      await submit(user2, user2Encryption, 648, 1, 2, 5);
+      const batchId = await triggerMatching();
+      const claim = {
+        matchId: new BN(0),
+        buyer: user1.publicKey,
+        seller: user2.publicKey,
+        buyerOrderId: new BN(446),
+        sellerOrderId: new BN(648),
+        quantity: new BN(2),
+        executionPrice: new BN(5),
+        buyerFilled: true,
+        sellerFilled: true,
+        buyerOwed: new BN(10),
+        sellerOwed: new BN(2),
+      };
+
+      // The previous key can neither commit nor execute once rotated out
+      await expectError(commitSettlement(batchId, claim), "UnauthorizedSettlement");
+      await awaitFinalization(await commitSettlement(batchId, claim, opsKey));
+      await expectError(executeSettlement(batchId, claim), "UnauthorizedSettlement");
+
+      await executeSettlement(batchId, claim, opsKey);
+      expect((await fetchOrder(446, user1)).status).to.equal(4);
+      expect((await fetchOrder(648, user2)).status).to.equal(4);
+
+      await setSettlementAuthority(authority.publicKey);
+      await setSettlementMode(1);
+    });
+  });
 });